@@ -123,8 +123,7 @@ impl Atlas {
             let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as i32;
             let padding = (align - data.rect.width % align) % align;
             let padded_width = data.rect.width + padding;
-            let mut padded_data = vec![];
-            padded_data.reserve((padded_width * data.rect.height) as usize);
+            let mut padded_data = Vec::with_capacity((padded_width * data.rect.height) as usize);
 
             let mut i = 0;
             for _ in 0..data.rect.height {
@@ -193,3 +192,189 @@ impl Atlas {
         self.did_clear = true;
     }
 }
+
+/// Regions of the image atlas. Deleted images' regions are reused for
+/// images that fit in them, from the frame after they were freed, since
+/// paints already made for the deleted images still point at them.
+struct ImageRegions {
+    packer: Packer,
+    area_used: i32,
+
+    /// Freed this frame.
+    freed: Vec<Rect>,
+
+    /// Free to reuse.
+    free: Vec<Rect>,
+
+    /// Number of regions in use.
+    used: usize,
+}
+
+impl ImageRegions {
+    fn new() -> Self {
+        Self {
+            packer: Packer::new(ImageAtlas::get_packer_config()),
+            area_used: 0,
+            freed: vec![],
+            free: vec![],
+            used: 0,
+        }
+    }
+
+    fn area(rect: &Rect) -> i32 {
+        (rect.width + ImageAtlas::RECT_PADDING) * (rect.height + ImageAtlas::RECT_PADDING)
+    }
+
+    /// Reuses the smallest free region the size fits in, or packs a
+    /// new one.
+    fn add(&mut self, width: i32, height: i32) -> Option<Rect> {
+        let reused = self
+            .free
+            .iter()
+            .enumerate()
+            .filter(|(_, rect)| rect.width >= width && rect.height >= height)
+            .min_by_key(|(_, rect)| rect.width * rect.height)
+            .map(|(i, _)| i);
+
+        let rect = match reused {
+            Some(i) => self.free.swap_remove(i),
+            None => self.packer.pack(width, height, false)?,
+        };
+        self.area_used += Self::area(&rect);
+        self.used += 1;
+
+        Some(rect)
+    }
+
+    fn remove(&mut self, rect: Rect) {
+        self.area_used -= Self::area(&rect);
+        self.used -= 1;
+        self.freed.push(rect);
+    }
+
+    /// Makes regions freed last frame reusable. Once every region is
+    /// free, the atlas is repacked from scratch.
+    fn recycle(&mut self) {
+        if self.used == 0 {
+            *self = Self::new();
+        } else {
+            self.free.append(&mut self.freed);
+        }
+    }
+}
+
+/// RGBA sibling of `Atlas` for packing small images into one texture,
+/// so that drawing them doesn't require switching bind groups.
+///
+/// Unlike `Atlas`, regions are written directly through the queue, so
+/// an image can be updated right after it's added.
+pub struct ImageAtlas {
+    regions: ImageRegions,
+    pub atlas_texture: wgpu::Texture,
+}
+
+impl ImageAtlas {
+    pub const ATLAS_SIZE: u32 = 1024;
+    pub const RECT_PADDING: i32 = 2;
+
+    /// Largest image, in either dimension, that is packed.
+    pub const MAX_IMAGE_SIZE: u32 = 128;
+
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+    fn get_packer_config() -> rect_packer::Config {
+        rect_packer::Config {
+            width: ImageAtlas::ATLAS_SIZE as i32,
+            height: ImageAtlas::ATLAS_SIZE as i32,
+
+            border_padding: ImageAtlas::RECT_PADDING,
+            rectangle_padding: ImageAtlas::RECT_PADDING,
+        }
+    }
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        let atlas_texture = device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width: ImageAtlas::ATLAS_SIZE,
+                height: ImageAtlas::ATLAS_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: ImageAtlas::FORMAT,
+            usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+            label: Some("image_atlas_texture"),
+            view_formats: &[ImageAtlas::FORMAT],
+        });
+
+        Self {
+            regions: ImageRegions::new(),
+            atlas_texture,
+        }
+    }
+
+    /// Reserves space for an image, returning its region in the atlas,
+    /// which may be larger than the image. Returns `None` if the image
+    /// is too large or the atlas is full.
+    pub fn add_region(&mut self, width: u32, height: u32) -> Option<Rect> {
+        if width > ImageAtlas::MAX_IMAGE_SIZE || height > ImageAtlas::MAX_IMAGE_SIZE {
+            return None;
+        }
+
+        self.regions.add(width as i32, height as i32)
+    }
+
+    /// Frees a region from `add_region`. It can be reused after the
+    /// next call to `recycle`.
+    pub fn remove_region(&mut self, rect: Rect) {
+        self.regions.remove(rect);
+    }
+
+    /// Called at the start of each frame.
+    pub fn recycle(&mut self) {
+        self.regions.recycle();
+    }
+
+    pub fn create_view(&self) -> wgpu::TextureView {
+        self.atlas_texture
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    pub fn usage(&self) -> f32 {
+        (self.regions.area_used as f32) / ((ImageAtlas::ATLAS_SIZE * ImageAtlas::ATLAS_SIZE) as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_image_regions() {
+        let mut regions = ImageRegions::new();
+
+        // 7x7 128 pixel images fit with padding.
+        let mut rects: Vec<_> = (0..49).map(|_| regions.add(128, 128).unwrap()).collect();
+        assert!(regions.add(128, 128).is_none());
+
+        // Freed regions are reused from the next frame.
+        let freed = rects.pop().unwrap();
+        regions.remove(freed);
+        assert!(regions.add(120, 120).is_none());
+        regions.recycle();
+        let reused = regions.add(120, 120).unwrap();
+        assert_eq!(reused, freed);
+        assert!(regions.add(120, 120).is_none());
+
+        // Once everything is freed, the atlas is repacked.
+        regions.remove(reused);
+        for rect in rects {
+            regions.remove(rect);
+        }
+        regions.recycle();
+        assert_eq!(regions.area_used, 0);
+        assert!(regions.add(16, 16).is_some());
+    }
+}
@@ -0,0 +1,67 @@
+use crate::color::Color;
+use crate::{FontId, PaintIndex};
+
+/// How a span of text is filled.
+#[derive(Copy, Clone, Debug)]
+pub enum TextFill {
+    Color(Color),
+
+    /// A paint from this frame, evaluated in the text's local space.
+    Paint(PaintIndex),
+}
+
+impl From<Color> for TextFill {
+    fn from(color: Color) -> Self {
+        TextFill::Color(color)
+    }
+}
+
+impl From<PaintIndex> for TextFill {
+    fn from(paint: PaintIndex) -> Self {
+        TextFill::Paint(paint)
+    }
+}
+
+/// A run of text in one style.
+#[derive(Clone, Debug)]
+pub struct TextSpan {
+    pub text: String,
+    pub font: FontId,
+    pub size: f32,
+    pub fill: TextFill,
+}
+
+/// Text made of styled spans, laid out together as one paragraph by
+/// `Vger::attributed_text`. Lines wrap across spans, and each line is
+/// as tall as its largest span.
+#[derive(Clone, Debug, Default)]
+pub struct AttributedText {
+    pub spans: Vec<TextSpan>,
+}
+
+impl AttributedText {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a span.
+    pub fn push(&mut self, text: &str, font: FontId, size: f32, fill: impl Into<TextFill>) {
+        self.spans.push(TextSpan {
+            text: text.to_string(),
+            font,
+            size,
+            fill: fill.into(),
+        });
+    }
+
+    /// Appends a span, for building text in one expression.
+    pub fn with(mut self, text: &str, font: FontId, size: f32, fill: impl Into<TextFill>) -> Self {
+        self.push(text, font, size, fill);
+        self
+    }
+
+    /// The text of all the spans.
+    pub fn text(&self) -> String {
+        self.spans.iter().map(|span| span.text.as_str()).collect()
+    }
+}
@@ -140,3 +140,80 @@ const fn hex_digit(x: u8) -> usize {
         panic!("bad hex digit")
     }) as usize
 }
+
+/// 4x5 matrix transforming straight alpha RGBA colors, as in SVG's
+/// feColorMatrix. Each row computes an output channel from r, g, b, a
+/// and a constant offset.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ColorMatrix {
+    pub rows: [[f32; 5]; 4],
+}
+
+impl ColorMatrix {
+    pub const IDENTITY: ColorMatrix = ColorMatrix {
+        rows: [
+            [1.0, 0.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0, 0.0],
+        ],
+    };
+
+    /// Scales saturation: 0 is grayscale, 1 leaves colors unchanged.
+    pub fn saturation(s: f32) -> Self {
+        let (r, g, b) = (0.213, 0.715, 0.072);
+        Self {
+            rows: [
+                [r + (1.0 - r) * s, g - g * s, b - b * s, 0.0, 0.0],
+                [r - r * s, g + (1.0 - g) * s, b - b * s, 0.0, 0.0],
+                [r - r * s, g - g * s, b + (1.0 - b) * s, 0.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0, 0.0],
+            ],
+        }
+    }
+
+    pub fn grayscale() -> Self {
+        Self::saturation(0.0)
+    }
+
+    /// Scales the color channels, leaving alpha alone.
+    pub fn brightness(scale: f32) -> Self {
+        let mut m = Self::IDENTITY;
+        for i in 0..3 {
+            m.rows[i][i] = scale;
+        }
+        m
+    }
+
+    /// The matrix applying self, then next.
+    pub fn then(&self, next: &ColorMatrix) -> Self {
+        let mut rows = [[0.0; 5]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| next.rows[i][k] * self.rows[k][j]).sum();
+            }
+            row[4] += next.rows[i][4];
+        }
+        Self { rows }
+    }
+
+    pub fn apply(&self, c: Color) -> Color {
+        let v = [c.r, c.g, c.b, c.a, 1.0];
+        let channel = |i: usize| {
+            let row: &[f32; 5] = &self.rows[i];
+            row.iter().zip(v.iter()).map(|(m, x)| m * x).sum::<f32>()
+        };
+        Color::new(channel(0), channel(1), channel(2), channel(3))
+    }
+
+    /// Column major mat4x4 followed by the offset vector.
+    pub(crate) fn to_gpu(self) -> [f32; 20] {
+        let mut data = [0.0; 20];
+        for column in 0..5 {
+            for row in 0..4 {
+                data[column * 4 + row] = self.rows[row][column];
+            }
+        }
+        data
+    }
+}
@@ -0,0 +1,306 @@
+//! PNG and JPEG decoding for `Vger::create_image_from_bytes`.
+
+use crate::image::ImageError;
+
+/// Decoded 8-bit sRGB RGBA pixels with straight alpha, which is what
+/// vger's blending expects.
+pub(crate) struct DecodedImage {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const JPEG_SIGNATURE: &[u8] = &[0xFF, 0xD8, 0xFF];
+
+/// Decodes a PNG or JPEG file, detected from its signature. Images
+/// wider or taller than `max_dimension` are rejected before their pixels
+/// are decoded.
+///
+/// Orientation metadata isn't applied: the first row of the file is the
+/// top of the image, which is the default when there is no EXIF
+/// orientation.
+pub(crate) fn decode(bytes: &[u8], max_dimension: u32) -> Result<DecodedImage, ImageError> {
+    let mut image = if bytes.starts_with(PNG_SIGNATURE) {
+        decode_png(bytes, max_dimension)?
+    } else if bytes.starts_with(JPEG_SIGNATURE) {
+        decode_jpeg(bytes, max_dimension)?
+    } else {
+        return Err(ImageError::UnknownFormat);
+    };
+
+    bleed_transparent(&mut image.data, image.width, image.height);
+    Ok(image)
+}
+
+/// Checks the size of an image from its header, returning the length of
+/// its RGBA pixels.
+fn rgba_len(width: u32, height: u32, max_dimension: u32) -> Result<usize, ImageError> {
+    let invalid = || ImageError::InvalidSize {
+        width,
+        height,
+        max: max_dimension,
+    };
+    if width == 0 || height == 0 || width > max_dimension || height > max_dimension {
+        return Err(invalid());
+    }
+    (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(4))
+        .ok_or_else(invalid)
+}
+
+fn decode_png(bytes: &[u8], max_dimension: u32) -> Result<DecodedImage, ImageError> {
+    let mut decoder = png::Decoder::new(bytes);
+
+    // Expands palettes, transparency chunks and low bit depths, and
+    // strips 16 bit channels down to 8.
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+    let mut reader = decoder
+        .read_info()
+        .map_err(|err| ImageError::Decode(err.to_string()))?;
+    let header = reader.info();
+    let len = rgba_len(header.width, header.height, max_dimension)?;
+
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buf)
+        .map_err(|err| ImageError::Decode(err.to_string()))?;
+
+    let channels = match info.color_type {
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::Rgb => 3,
+        png::ColorType::Rgba => 4,
+        png::ColorType::Indexed => {
+            return Err(ImageError::Decode("unexpanded palette".to_string()))
+        }
+    };
+
+    let mut data = Vec::with_capacity(len);
+    for row in buf.chunks(info.line_size).take(info.height as usize) {
+        let row = &row[..info.width as usize * channels];
+        data.extend(row.chunks(channels).flat_map(to_rgba));
+    }
+
+    Ok(DecodedImage {
+        width: info.width,
+        height: info.height,
+        data,
+    })
+}
+
+fn decode_jpeg(bytes: &[u8], max_dimension: u32) -> Result<DecodedImage, ImageError> {
+    let mut decoder = jpeg_decoder::Decoder::new(bytes);
+    decoder
+        .read_info()
+        .map_err(|err| ImageError::Decode(err.to_string()))?;
+    let info = decoder
+        .info()
+        .ok_or_else(|| ImageError::Decode("missing image info".to_string()))?;
+    rgba_len(info.width as u32, info.height as u32, max_dimension)?;
+
+    let pixels = decoder
+        .decode()
+        .map_err(|err| ImageError::Decode(err.to_string()))?;
+
+    let data = match info.pixel_format {
+        jpeg_decoder::PixelFormat::L8 => pixels.chunks(1).flat_map(to_rgba).collect(),
+        jpeg_decoder::PixelFormat::L16 => {
+            // Only lossless JPEGs with 9 to 16 bits per sample are L16.
+            let precision = jpeg_precision(bytes).unwrap_or(16);
+            pixels
+                .chunks(2)
+                .flat_map(|p| to_rgba(&[to_8_bit(u16::from_ne_bytes([p[0], p[1]]), precision)]))
+                .collect()
+        }
+        jpeg_decoder::PixelFormat::RGB24 => pixels.chunks(3).flat_map(to_rgba).collect(),
+        jpeg_decoder::PixelFormat::CMYK32 => pixels.chunks(4).flat_map(cmyk_to_rgba).collect(),
+    };
+
+    Ok(DecodedImage {
+        width: info.width as u32,
+        height: info.height as u32,
+        data,
+    })
+}
+
+/// Bits per sample from a JPEG's frame header, which jpeg-decoder
+/// doesn't report.
+fn jpeg_precision(bytes: &[u8]) -> Option<u8> {
+    // Segments after the SOI marker, each a marker and a big-endian
+    // length that counts itself.
+    let mut pos = 2;
+    while pos + 4 < bytes.len() {
+        if bytes[pos] != 0xFF {
+            return None;
+        }
+        let marker = bytes[pos + 1];
+        match marker {
+            // Fill bytes.
+            0xFF => {
+                pos += 1;
+                continue;
+            }
+            // Start of frame, except DHT, JPG and DAC which share the range.
+            0xC0..=0xCF if marker != 0xC4 && marker != 0xC8 && marker != 0xCC => {
+                return Some(bytes[pos + 4]);
+            }
+            // Start of scan or end of image.
+            0xDA | 0xD9 => return None,
+            _ => (),
+        }
+        let length = u16::from_be_bytes([bytes[pos + 2], bytes[pos + 3]]) as usize;
+        pos += 2 + length;
+    }
+    None
+}
+
+/// Scales a sample with `precision` bits to 8 bits.
+fn to_8_bit(sample: u16, precision: u8) -> u8 {
+    let max = (1_u32 << precision) - 1;
+    ((sample as u32).min(max) * 255 / max) as u8
+}
+
+/// Expands a gray, gray alpha, RGB or RGBA pixel to RGBA.
+fn to_rgba(p: &[u8]) -> [u8; 4] {
+    match *p {
+        [l] => [l, l, l, 255],
+        [l, a] => [l, l, l, a],
+        [r, g, b] => [r, g, b, 255],
+        [r, g, b, a] => [r, g, b, a],
+        _ => unreachable!(),
+    }
+}
+
+fn cmyk_to_rgba(p: &[u8]) -> [u8; 4] {
+    let k = p[3] as u32;
+    let channel = |c: u8| ((255 - c as u32) * (255 - k) / 255) as u8;
+    [channel(p[0]), channel(p[1]), channel(p[2]), 255]
+}
+
+/// vger blends with straight alpha, so decoded pixels aren't
+/// premultiplied, and the color of transparent pixels still shows
+/// through when filtering. Gives fully transparent pixels the average
+/// color of their visible neighbors, which avoids dark fringes around
+/// cutouts, as premultiplied filtering would.
+fn bleed_transparent(data: &mut [u8], width: u32, height: u32) {
+    let (width, height) = (width as i64, height as i64);
+    let source = data.to_vec();
+
+    for y in 0..height {
+        for x in 0..width {
+            let i = ((y * width + x) * 4) as usize;
+            if source[i + 3] != 0 {
+                continue;
+            }
+
+            let mut sum = [0u32; 3];
+            let mut count = 0;
+            for (dx, dy) in (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (dx, dy))) {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || nx >= width || ny >= height {
+                    continue;
+                }
+                let j = ((ny * width + nx) * 4) as usize;
+                if source[j + 3] != 0 {
+                    for c in 0..3 {
+                        sum[c] += source[j + c] as u32;
+                    }
+                    count += 1;
+                }
+            }
+
+            for c in 0..3 {
+                if let Some(average) = sum[c].checked_div(count) {
+                    data[i + c] = average as u8;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_to_rgba() {
+        assert_eq!(to_rgba(&[7]), [7, 7, 7, 255]);
+        assert_eq!(to_rgba(&[7, 9]), [7, 7, 7, 9]);
+        assert_eq!(to_rgba(&[1, 2, 3]), [1, 2, 3, 255]);
+        assert_eq!(cmyk_to_rgba(&[0, 255, 255, 0]), [255, 0, 0, 255]);
+        assert_eq!(cmyk_to_rgba(&[0, 0, 0, 255]), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_jpeg_precision() {
+        // SOI, an APP0 segment with two bytes of data, then a lossless
+        // SOF3 header with 12 bit samples.
+        let bytes = [
+            0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, 0xFF, 0xC3, 0x00, 0x0B, 0x0C, 0x00,
+            0x01,
+        ];
+        assert_eq!(jpeg_precision(&bytes), Some(12));
+        assert_eq!(jpeg_precision(&bytes[..8]), None);
+
+        assert_eq!(to_8_bit(4095, 12), 255);
+        assert_eq!(to_8_bit(2048, 12), 127);
+        assert_eq!(to_8_bit(0, 12), 0);
+        assert_eq!(to_8_bit(65535, 16), 255);
+        assert_eq!(to_8_bit(511, 9), 255);
+    }
+
+    #[test]
+    fn test_bleed_transparent() {
+        // Opaque red next to transparent black.
+        let mut data = vec![255, 0, 0, 255, 0, 0, 0, 0];
+        bleed_transparent(&mut data, 2, 1);
+        assert_eq!(data, [255, 0, 0, 255, 255, 0, 0, 0]);
+
+        // Nothing visible nearby.
+        let mut data = vec![0; 8];
+        bleed_transparent(&mut data, 2, 1);
+        assert_eq!(data, [0; 8]);
+    }
+
+    #[test]
+    fn test_unknown_format() {
+        assert_eq!(
+            decode(b"GIF89a", 8192).err(),
+            Some(ImageError::UnknownFormat)
+        );
+    }
+
+    #[test]
+    fn test_rgba_len() {
+        assert_eq!(rgba_len(3, 2, 8192), Ok(24));
+        let too_wide = ImageError::InvalidSize {
+            width: 8193,
+            height: 1,
+            max: 8192,
+        };
+        assert_eq!(rgba_len(8193, 1, 8192), Err(too_wide));
+        assert!(rgba_len(0, 1, 8192).is_err());
+
+        // Images past the size limit are rejected.
+        let mut bytes = vec![];
+        {
+            let mut encoder = png::Encoder::new(&mut bytes, 4, 4);
+            encoder.set_color(png::ColorType::Rgba);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[0; 64]).unwrap();
+        }
+        assert!(decode(&bytes, 4).is_ok());
+        assert_eq!(
+            decode(&bytes, 2).err(),
+            Some(ImageError::InvalidSize {
+                width: 4,
+                height: 4,
+                max: 2
+            })
+        );
+    }
+}
@@ -16,3 +16,7 @@ pub type WorldToLocal = Transform2D<f32, WorldSpace, LocalSpace>;
 pub type LocalTransform = Transform2D<f32, LocalSpace, LocalSpace>;
 
 pub type LocalRect = Rect<f32, LocalSpace>;
+
+pub struct ImageSpace;
+pub type ImageRect = Rect<u32, ImageSpace>;
+pub type ImageInsets = SideOffsets2D<u32, ImageSpace>;
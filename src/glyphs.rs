@@ -1,68 +1,224 @@
 use crate::atlas::Atlas;
+use ab_glyph_rasterizer::{point, Point, Rasterizer};
+use fontdue::layout::GlyphRasterConfig;
 use rect_packer::Rect;
 use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+
+/// Number of horizontal positions within a pixel glyphs are rasterized
+/// at.
+pub const SUBPIXEL_PHASES: u8 = 4;
 
 #[derive(Copy, Clone, Debug)]
 pub struct GlyphInfo {
     pub rect: Option<Rect>,
-    pub metrics: fontdue::Metrics,
+
+    /// Offset of the bitmap's bottom left from the pen position, in
+    /// device pixels.
+    pub left: i32,
+    pub bottom: i32,
+}
+
+/// Errors from adding fonts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FontError {
+    /// The data couldn't be parsed as a TrueType or OpenType font.
+    Invalid(String),
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FontError::Invalid(message) => write!(f, "invalid font: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for FontError {}
+
+/// Snaps a pen position in device pixels to a whole pixel vertically
+/// and to a subpixel phase horizontally, returning the whole pixel pen
+/// and the phase.
+pub fn snap_pen(pen: [f32; 2]) -> ([f32; 2], u8) {
+    let phases = SUBPIXEL_PHASES as f32;
+    let x = (pen[0] * phases).round() / phases;
+    let whole = x.floor();
+    ([whole, pen[1].round()], ((x - whole) * phases) as u8)
 }
 
 pub struct GlyphCache {
     pub atlas: Atlas,
-    pub font: fontdue::Font,
-    info: HashMap<(char, u32), GlyphInfo>,
+
+    /// Fonts by `FontId`. The first two are built in.
+    pub fonts: Vec<fontdue::Font>,
+
+    /// Font files by `FontId`, for rasterizing and shaping.
+    pub font_data: Vec<Vec<u8>>,
+
+    /// For each font, the fonts tried in order for characters it
+    /// lacks.
+    fallbacks: Vec<Vec<usize>>,
+    info: HashMap<(GlyphRasterConfig, u8), GlyphInfo>,
+
+    /// Pixel bounds from `pixel_bounds`, so layout doesn't parse the
+    /// font for every glyph.
+    bounds: HashMap<(GlyphRasterConfig, u8), Option<[f32; 4]>>,
 }
 
 impl GlyphCache {
     pub fn new(device: &wgpu::Device) -> Self {
-        let mut settings = fontdue::FontSettings::default();
-        settings.collection_index = 0;
-        settings.scale = 100.0;
         let font = include_bytes!("fonts/Anodina-Regular.ttf") as &[u8];
+        let fallback = include_bytes!("fonts/Atkinson-Hyperlegible-Regular-102.ttf") as &[u8];
 
-        Self {
+        let mut cache = Self {
             atlas: Atlas::new(device),
-            font: fontdue::Font::from_bytes(font, settings).unwrap(),
+            fonts: vec![],
+            font_data: vec![],
+            fallbacks: vec![],
             info: HashMap::new(),
+            bounds: HashMap::new(),
+        };
+        cache.add_font(font).unwrap();
+        let fallback = cache.add_font(fallback).unwrap();
+
+        // Atkinson Hyperlegible adds some Greek letters and math
+        // symbols.
+        cache.set_fallbacks(0, vec![fallback]);
+        cache
+    }
+
+    fn load_font(bytes: &[u8]) -> Result<fontdue::Font, FontError> {
+        let settings = fontdue::FontSettings {
+            collection_index: 0,
+            scale: 100.0,
+            ..fontdue::FontSettings::default()
+        };
+        fontdue::Font::from_bytes(bytes, settings)
+            .map_err(|message| FontError::Invalid(message.to_string()))
+    }
+
+    /// Adds a font, returning its index in `fonts`.
+    pub fn add_font(&mut self, bytes: &[u8]) -> Result<usize, FontError> {
+        let font = Self::load_font(bytes)?;
+        ttf_parser::Face::parse(bytes, 0).map_err(|error| FontError::Invalid(error.to_string()))?;
+
+        #[cfg(feature = "shaping")]
+        if rustybuzz::Face::from_slice(bytes, 0).is_none() {
+            return Err(FontError::Invalid("unsupported by the shaper".into()));
         }
+
+        self.font_data.push(bytes.to_vec());
+        self.fonts.push(font);
+        self.fallbacks.push(vec![]);
+        Ok(self.fonts.len() - 1)
     }
 
-    pub fn get_glyph(&mut self, c: char, size: f32) -> GlyphInfo {
-        let factor = 65536.0;
+    pub fn set_fallbacks(&mut self, font: usize, fallbacks: Vec<usize>) {
+        self.fallbacks[font] = fallbacks;
+    }
+
+    /// The first of font and its fallbacks that has a glyph for c, or
+    /// font itself if none do.
+    pub fn font_for_char(&self, font: usize, c: char) -> usize {
+        std::iter::once(&font)
+            .chain(&self.fallbacks[font])
+            .copied()
+            .find(|&f| self.fonts[f].lookup_glyph_index(c) != 0)
+            .unwrap_or(font)
+    }
 
-        // Convert size to fixed point so we can hash it.
-        let size_fixed_point = (size * factor) as u32;
+    /// Splits text into runs of characters with the same font from
+    /// `font_for_char`, returning byte ranges and font indices.
+    pub fn font_runs(&self, text: &str, font: usize) -> Vec<(Range<usize>, usize)> {
+        let mut runs: Vec<(Range<usize>, usize)> = vec![];
+        for (i, c) in text.char_indices() {
+            let char_font = self.font_for_char(font, c);
+            match runs.last_mut() {
+                Some((range, run_font)) if *run_font == char_font => {
+                    range.end = i + c.len_utf8();
+                }
+                _ => runs.push((i..i + c.len_utf8(), char_font)),
+            }
+        }
+        runs
+    }
 
+    /// Rounds a font size in device pixels so text whose size changes
+    /// continuously, such as while zooming, reuses glyphs instead of
+    /// filling the atlas. Sizes up to 16px round to quarter pixels.
+    /// Above that the step doubles with each doubling of size, which
+    /// keeps the change under 1%.
+    pub fn bucket_size(px: f32) -> f32 {
+        if px <= 16.0 {
+            return (px * 4.0).round() / 4.0;
+        }
+        let step = 0.25 * (px / 16.0).log2().floor().exp2();
+        (px / step).round() * step
+    }
+
+    /// Returns the glyph for a layout key, shifted right by `phase` out
+    /// of `SUBPIXEL_PHASES` of a pixel, rasterizing it with `font` if it
+    /// isn't cached. The key identifies the font by hash, so fonts
+    /// loaded twice share glyphs.
+    pub fn get_glyph(&mut self, font: usize, key: GlyphRasterConfig, phase: u8) -> GlyphInfo {
         // Do we already have a glyph?
-        match self.info.get(&(c, size_fixed_point)) {
+        match self.info.get(&(key, phase)) {
             Some(info) => *info,
             None => {
-                let (metrics, data) = self.font.rasterize(c, size_fixed_point as f32 / factor);
-
-                /*
-                let mut i = 0;
-                for _ in 0..metrics.height {
-                    for _ in 0..metrics.width {
-                        print!("{} ", if data[i] != 0 { '*' } else { ' ' });
-                        i += 1;
-                    }
-                    print!("\n");
-                }
-                */
+                let offset = phase as f32 / SUBPIXEL_PHASES as f32;
+                let bitmap = rasterize(&self.face(font), key.glyph_index, key.px, offset);
 
                 let rect =
                     self.atlas
-                        .add_region(&data, metrics.width as u32, metrics.height as u32);
+                        .add_region(&bitmap.data, bitmap.width as u32, bitmap.height as u32);
 
-                let info = GlyphInfo { rect, metrics };
+                let info = GlyphInfo {
+                    rect,
+                    left: bitmap.left,
+                    bottom: bitmap.bottom,
+                };
 
-                self.info.insert((c, size_fixed_point), info);
+                self.info.insert((key, phase), info);
                 info
             }
         }
     }
 
+    /// Bounds of the quad drawn for a glyph with its pen at `pen`, when
+    /// the pen is snapped with `snap_pen`. Returns the bottom left and
+    /// size in device pixels. Glyphs without outlines have empty bounds
+    /// at the snapped pen.
+    pub fn glyph_rect(
+        &mut self,
+        font: usize,
+        key: GlyphRasterConfig,
+        pen: [f32; 2],
+    ) -> ([f32; 2], [usize; 2]) {
+        let (pen, phase) = snap_pen(pen);
+        let bounds = match self.bounds.get(&(key, phase)) {
+            Some(bounds) => *bounds,
+            None => {
+                let offset = phase as f32 / SUBPIXEL_PHASES as f32;
+                let bounds = pixel_bounds(&self.face(font), key.glyph_index, key.px, offset);
+                self.bounds.insert((key, phase), bounds);
+                bounds
+            }
+        };
+        match bounds {
+            Some([left, bottom, right, top]) => (
+                [pen[0] + left, pen[1] + bottom],
+                [(right - left) as usize, (top - bottom) as usize],
+            ),
+            None => (pen, [0, 0]),
+        }
+    }
+
+    fn face(&self, font: usize) -> ttf_parser::Face<'_> {
+        // Fonts are checked when they're added.
+        ttf_parser::Face::parse(&self.font_data[font], 0).unwrap()
+    }
+
     pub fn update(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) {
         self.atlas.update(device, encoder);
     }
@@ -77,6 +233,157 @@ impl GlyphCache {
 
     pub fn clear(&mut self) {
         self.info.clear();
+        self.bounds.clear();
         self.atlas.clear();
     }
 }
+
+/// Coverage of a glyph, with rows from top to bottom.
+struct Bitmap {
+    left: i32,
+    bottom: i32,
+    width: usize,
+    height: usize,
+    data: Vec<u8>,
+}
+
+/// Pixel bounds of a glyph's bitmap relative to the pen, as left,
+/// bottom, right and top, with its outline shifted right by `offset`
+/// pixels. Returns `None` for glyphs without outlines.
+fn pixel_bounds(
+    face: &ttf_parser::Face,
+    glyph_index: u16,
+    px: f32,
+    offset: f32,
+) -> Option<[f32; 4]> {
+    let bounds = face.glyph_bounding_box(ttf_parser::GlyphId(glyph_index))?;
+    let scale = px / face.units_per_em() as f32;
+    Some([
+        (bounds.x_min as f32 * scale + offset).floor(),
+        (bounds.y_min as f32 * scale).floor(),
+        (bounds.x_max as f32 * scale + offset).ceil(),
+        (bounds.y_max as f32 * scale).ceil(),
+    ])
+}
+
+/// Rasterizes a glyph with its outline shifted right by `offset` pixels.
+/// fontdue can't do this: it computes the offset's effect on a glyph's
+/// metrics in a private function, and its public rasterize functions
+/// always pass an offset of zero.
+fn rasterize(face: &ttf_parser::Face, glyph_index: u16, px: f32, offset: f32) -> Bitmap {
+    let [left, bottom, right, top] = match pixel_bounds(face, glyph_index, px, offset) {
+        Some(bounds) => bounds,
+        None => {
+            return Bitmap {
+                left: 0,
+                bottom: 0,
+                width: 0,
+                height: 0,
+                data: vec![],
+            }
+        }
+    };
+    let width = (right - left) as usize;
+    let height = (top - bottom) as usize;
+
+    let mut outline = Outline {
+        rasterizer: Rasterizer::new(width, height),
+        scale: px / face.units_per_em() as f32,
+        dx: offset - left,
+        top,
+        start: point(0.0, 0.0),
+        last: point(0.0, 0.0),
+    };
+    face.outline_glyph(ttf_parser::GlyphId(glyph_index), &mut outline);
+
+    let mut data = vec![0; width * height];
+    outline.rasterizer.for_each_pixel(|i, coverage| {
+        data[i] = (coverage.min(1.0) * 255.0).round() as u8;
+    });
+
+    Bitmap {
+        left: left as i32,
+        bottom: bottom as i32,
+        width,
+        height,
+        data,
+    }
+}
+
+/// Draws an outline in font units into a bitmap whose rows go down from
+/// `top`.
+struct Outline {
+    rasterizer: Rasterizer,
+    scale: f32,
+    dx: f32,
+    top: f32,
+    start: Point,
+    last: Point,
+}
+
+impl Outline {
+    fn point(&self, x: f32, y: f32) -> Point {
+        point(x * self.scale + self.dx, self.top - y * self.scale)
+    }
+}
+
+impl ttf_parser::OutlineBuilder for Outline {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.start = self.point(x, y);
+        self.last = self.start;
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let p = self.point(x, y);
+        self.rasterizer.draw_line(self.last, p);
+        self.last = p;
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let p = self.point(x, y);
+        self.rasterizer.draw_quad(self.last, self.point(x1, y1), p);
+        self.last = p;
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let p = self.point(x, y);
+        self.rasterizer
+            .draw_cubic(self.last, self.point(x1, y1), self.point(x2, y2), p);
+        self.last = p;
+    }
+
+    fn close(&mut self) {
+        self.rasterizer.draw_line(self.last, self.start);
+        self.last = self.start;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_bucket_size() {
+        assert_eq!(GlyphCache::bucket_size(13.5), 13.5);
+        assert_eq!(GlyphCache::bucket_size(13.6), 13.5);
+        assert_eq!(GlyphCache::bucket_size(40.2), 40.0);
+        assert_eq!(GlyphCache::bucket_size(40.3), 40.5);
+
+        // Zooming from 10px to 100px in tiny steps.
+        let mut buckets = vec![];
+        for i in 0..=90_000 {
+            let px = 10.0 + i as f32 * 0.001;
+            let bucket = GlyphCache::bucket_size(px);
+            if px <= 16.0 {
+                assert!((bucket - px).abs() <= 0.125);
+            } else {
+                assert!((bucket - px).abs() / px < 0.01);
+            }
+            if buckets.last() != Some(&bucket) {
+                buckets.push(bucket);
+            }
+        }
+        assert!(buckets.len() < 200, "{} buckets", buckets.len());
+    }
+}
@@ -0,0 +1,766 @@
+use crate::color::{Color, ColorMatrix};
+use crate::defs::*;
+use crate::mipmap::MipmapGenerator;
+use std::fmt;
+
+/// Pixel formats accepted by `Vger::create_image_pixels`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// Single channel, e.g. a coverage mask. Sampled in the red channel.
+    R8,
+
+    /// Two channels, sampled in red and green.
+    Rg8,
+
+    /// 8-bit RGBA in the sRGB color space.
+    Rgba8Srgb,
+
+    /// 8-bit RGBA, linear.
+    Rgba8,
+
+    /// 8-bit BGRA in the sRGB color space.
+    Bgra8Srgb,
+
+    /// 16-bit float RGBA, linear. Each channel is a little-endian f16.
+    Rgba16F,
+}
+
+impl ImageFormat {
+    /// Size of one pixel in bytes.
+    pub fn bytes_per_pixel(&self) -> u32 {
+        match self {
+            ImageFormat::R8 => 1,
+            ImageFormat::Rg8 => 2,
+            ImageFormat::Rgba8Srgb | ImageFormat::Rgba8 | ImageFormat::Bgra8Srgb => 4,
+            ImageFormat::Rgba16F => 8,
+        }
+    }
+
+    pub(crate) fn texture_format(&self) -> wgpu::TextureFormat {
+        match self {
+            ImageFormat::R8 => wgpu::TextureFormat::R8Unorm,
+            ImageFormat::Rg8 => wgpu::TextureFormat::Rg8Unorm,
+            ImageFormat::Rgba8Srgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+            ImageFormat::Rgba8 => wgpu::TextureFormat::Rgba8Unorm,
+            ImageFormat::Bgra8Srgb => wgpu::TextureFormat::Bgra8UnormSrgb,
+            ImageFormat::Rgba16F => wgpu::TextureFormat::Rgba16Float,
+        }
+    }
+}
+
+/// Texture filtering for an image.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ImageFilter {
+    /// Blocky, for pixel art.
+    Nearest,
+
+    /// Smooth bilinear (trilinear with mipmaps) filtering.
+    Linear,
+}
+
+/// What happens when an image is sampled outside of its bounds.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ImageWrap {
+    /// Repeat the edge pixels.
+    Clamp,
+
+    /// Tile the image.
+    Repeat,
+
+    /// Tile the image, flipping every other copy.
+    Mirror,
+}
+
+/// How an image is sampled.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SamplerOptions {
+    pub filter: ImageFilter,
+    pub wrap: ImageWrap,
+
+    /// Generate a full mip chain, which keeps downscaled images from
+    /// aliasing.
+    pub mipmaps: bool,
+}
+
+impl Default for SamplerOptions {
+    fn default() -> Self {
+        Self {
+            filter: ImageFilter::Linear,
+            wrap: ImageWrap::Clamp,
+            mipmaps: false,
+        }
+    }
+}
+
+impl SamplerOptions {
+    pub(crate) fn create_sampler(&self, device: &wgpu::Device) -> wgpu::Sampler {
+        let filter = match self.filter {
+            ImageFilter::Nearest => wgpu::FilterMode::Nearest,
+            ImageFilter::Linear => wgpu::FilterMode::Linear,
+        };
+
+        let address_mode = match self.wrap {
+            ImageWrap::Clamp => wgpu::AddressMode::ClampToEdge,
+            ImageWrap::Repeat => wgpu::AddressMode::Repeat,
+            ImageWrap::Mirror => wgpu::AddressMode::MirrorRepeat,
+        };
+
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("image"),
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_filter: filter,
+            ..Default::default()
+        })
+    }
+}
+
+/// Plane layouts for YUV images. Chroma is subsampled by two in both
+/// directions.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum YuvFormat {
+    /// A Y plane followed by an interleaved UV plane.
+    Nv12,
+
+    /// Separate Y, U and V planes.
+    I420,
+}
+
+impl YuvFormat {
+    /// Formats of the planes.
+    pub fn plane_formats(&self) -> &'static [ImageFormat] {
+        match self {
+            YuvFormat::Nv12 => &[ImageFormat::R8, ImageFormat::Rg8],
+            YuvFormat::I420 => &[ImageFormat::R8, ImageFormat::R8, ImageFormat::R8],
+        }
+    }
+
+    /// Size of a plane in pixels, given the size of the Y plane.
+    pub fn plane_size(&self, plane: usize, width: u32, height: u32) -> [u32; 2] {
+        if plane == 0 {
+            [width, height]
+        } else {
+            [width.div_ceil(2), height.div_ceil(2)]
+        }
+    }
+}
+
+/// YUV to RGB conversion coefficients.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum YuvColorSpace {
+    /// Standard definition video.
+    Bt601,
+
+    /// HD video.
+    Bt709,
+}
+
+/// Range of YUV values.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum YuvRange {
+    /// Y in 16..=235 and UV in 16..=240, as most video uses.
+    Limited,
+
+    /// All of 0..=255, as JPEG uses.
+    Full,
+}
+
+/// Pixel data for one plane of a YUV image. `bytes_per_row` is as for
+/// `Vger::create_image_pixels`.
+#[derive(Copy, Clone, Debug)]
+pub struct YuvPlane<'a> {
+    pub data: &'a [u8],
+    pub bytes_per_row: Option<u32>,
+}
+
+impl<'a> YuvPlane<'a> {
+    /// A plane with tightly packed rows.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            bytes_per_row: None,
+        }
+    }
+}
+
+/// The matrix taking (Y, U, V, 1) to gamma encoded RGBA.
+pub(crate) fn yuv_to_rgb(color_space: YuvColorSpace, range: YuvRange) -> ColorMatrix {
+    let (kr, kb) = match color_space {
+        YuvColorSpace::Bt601 => (0.299, 0.114),
+        YuvColorSpace::Bt709 => (0.2126, 0.0722),
+    };
+    let kg = 1.0 - kr - kb;
+
+    // Scale and offset normalizing Y to 0..1 and UV to -0.5..0.5.
+    let (y_scale, y_offset, c_scale) = match range {
+        YuvRange::Limited => (255.0 / 219.0, 16.0 / 255.0, 255.0 / 224.0),
+        YuvRange::Full => (1.0, 0.0, 1.0),
+    };
+    let c_offset = 128.0 / 255.0;
+
+    // Coefficients of normalized Y, U and V.
+    let rows = [
+        [1.0, 0.0, 2.0 * (1.0 - kr)],
+        [
+            1.0,
+            -2.0 * kb * (1.0 - kb) / kg,
+            -2.0 * kr * (1.0 - kr) / kg,
+        ],
+        [1.0, 2.0 * (1.0 - kb), 0.0],
+    ];
+
+    let mut m = ColorMatrix::IDENTITY;
+    for (i, [y, u, v]) in rows.iter().enumerate() {
+        m.rows[i] = [
+            y * y_scale,
+            u * c_scale,
+            v * c_scale,
+            0.0,
+            -(y * y_scale * y_offset + (u + v) * c_scale * c_offset),
+        ];
+    }
+    m
+}
+
+/// Color adjustments for image paints, see `Vger::adjust_image_paint`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ImageAdjustments {
+    /// Multiplies the paint's alpha.
+    pub opacity: f32,
+
+    /// Multiplies the image's colors.
+    pub tint: Color,
+
+    /// Applied to the sampled color before tint and opacity.
+    pub color_matrix: Option<ColorMatrix>,
+}
+
+impl Default for ImageAdjustments {
+    fn default() -> Self {
+        Self {
+            opacity: 1.0,
+            tint: Color::WHITE,
+            color_matrix: None,
+        }
+    }
+}
+
+/// How `Vger::draw_image` fits an image into the destination rectangle.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImageFit {
+    /// Stretch to fill the destination, ignoring aspect ratio.
+    Fill,
+
+    /// Scale uniformly to fit inside the destination, centered.
+    Contain,
+
+    /// Scale uniformly to cover the destination, centered and cropped.
+    Cover,
+
+    /// Draw at one local unit per pixel, centered and cropped.
+    None,
+}
+
+impl ImageFit {
+    /// Returns where the whole source region lands for a destination
+    /// rectangle. May extend past `dst` for `Cover` and `None`.
+    pub fn place(&self, src_size: LocalSize, dst: LocalRect) -> LocalRect {
+        let size = match self {
+            ImageFit::Fill => return dst,
+            ImageFit::Contain | ImageFit::Cover => {
+                let sx = dst.size.width / src_size.width;
+                let sy = dst.size.height / src_size.height;
+                let s = if *self == ImageFit::Contain {
+                    sx.min(sy)
+                } else {
+                    sx.max(sy)
+                };
+                src_size * s
+            }
+            ImageFit::None => src_size,
+        };
+
+        LocalRect::new(dst.center() - size.to_vector() * 0.5, size)
+    }
+}
+
+/// How `Vger::draw_image_nine_slice` fills the edges and center.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SliceMode {
+    /// Stretch the edges and center to fit.
+    Stretch,
+
+    /// Repeat the edges and center at their natural size.
+    Tile,
+}
+
+/// Errors from creating or updating images.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ImageError {
+    /// Width or height is zero or exceeds the device's texture size limit.
+    InvalidSize { width: u32, height: u32, max: u32 },
+
+    /// Source rows are shorter than a row of pixels, or not a whole
+    /// number of pixels.
+    InvalidStride { bytes_per_row: u32, min: u32 },
+
+    /// Not enough pixel data for the image.
+    DataTooShort { len: usize, expected: usize },
+
+    /// The image index doesn't refer to a live image.
+    InvalidImage,
+
+    /// Nine-slice insets overlap.
+    InvalidInsets,
+
+    /// The image is a registered texture, whose pixels are owned by
+    /// the caller.
+    ExternalTexture,
+
+    /// The wrong number of planes was given for a YUV image.
+    InvalidPlanes { expected: usize, found: usize },
+
+    /// YUV planes were given for an image that isn't YUV.
+    NotYuv,
+
+    /// YUV updates must start on even pixels and have even sizes,
+    /// except at the right and bottom edges, so chroma samples aren't
+    /// split.
+    UnalignedRect,
+
+    /// The paint index is past the paints created this frame.
+    InvalidPaint,
+
+    /// The paint already draws an image, so it can't also be masked by
+    /// one. Only one image is bound per draw.
+    MaskedImagePaint,
+
+    /// The data isn't a PNG or JPEG file.
+    UnknownFormat,
+
+    /// The PNG or JPEG data couldn't be decoded.
+    Decode(String),
+
+    /// The rectangle extends past the edge of the image.
+    OutOfBounds {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::InvalidSize { width, height, max } => {
+                write!(f, "invalid image size {}x{} (max {})", width, height, max)
+            }
+            ImageError::InvalidStride { bytes_per_row, min } => write!(
+                f,
+                "invalid stride of {} bytes per row (need at least {}, in whole pixels)",
+                bytes_per_row, min
+            ),
+            ImageError::DataTooShort { len, expected } => write!(
+                f,
+                "image data is {} bytes, expected at least {}",
+                len, expected
+            ),
+            ImageError::InvalidImage => write!(f, "invalid image index"),
+            ImageError::InvalidInsets => write!(f, "nine-slice insets are larger than the image"),
+            ImageError::ExternalTexture => {
+                write!(f, "operation not supported on a registered texture")
+            }
+            ImageError::InvalidPlanes { expected, found } => {
+                write!(f, "expected {} image planes, found {}", expected, found)
+            }
+            ImageError::NotYuv => write!(f, "the image is not a YUV image"),
+            ImageError::UnalignedRect => {
+                write!(f, "YUV image rectangles must be aligned to chroma samples")
+            }
+            ImageError::InvalidPaint => write!(f, "paint index is not from this frame"),
+            ImageError::MaskedImagePaint => write!(f, "image paints can't be masked"),
+            ImageError::UnknownFormat => write!(f, "unknown image file format"),
+            ImageError::Decode(message) => write!(f, "image decoding failed: {}", message),
+            ImageError::OutOfBounds {
+                x,
+                y,
+                width,
+                height,
+            } => write!(
+                f,
+                "rectangle {}x{} at ({}, {}) is outside the image",
+                width, height, x, y
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+/// Paint image index for images packed into the image atlas.
+pub(crate) const ATLAS_IMAGE: i32 = -2;
+
+/// Where an image's pixels live.
+pub(crate) enum ImageStorage {
+    /// A texture of its own.
+    Texture {
+        texture: wgpu::Texture,
+        bind_group: wgpu::BindGroup,
+    },
+
+    /// A region of the image atlas, at the given origin in pixels.
+    /// `reserved` is the size of the region, which may be larger than
+    /// the image.
+    Atlas {
+        origin: [u32; 2],
+        reserved: [u32; 2],
+    },
+
+    /// A texture owned by the caller, see `Vger::register_texture`.
+    External { bind_group: wgpu::BindGroup },
+
+    /// One texture per plane, converted to RGB in the shader.
+    Yuv {
+        format: YuvFormat,
+        info: ImageInfo,
+        planes: Vec<wgpu::Texture>,
+        bind_group: wgpu::BindGroup,
+    },
+}
+
+/// Per-image shader parameters, bound alongside the image's texture.
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+pub(crate) struct ImageInfo {
+    /// 0 for RGBA images, 1 for NV12 and 2 for I420.
+    pub yuv: u32,
+
+    /// The rest of the header is only used with `ImageArray`: the
+    /// image's generation, its sampler and the array element of its
+    /// first chroma plane.
+    pub generation: u32,
+    pub sampler_index: u32,
+    pub chroma: u32,
+
+    /// YUV to RGB conversion, see `ColorMatrix::to_gpu`.
+    pub matrix: [f32; 20],
+}
+
+impl Default for ImageInfo {
+    fn default() -> Self {
+        Self {
+            yuv: 0,
+            generation: 0,
+            sampler_index: 0,
+            chroma: 0,
+            matrix: ColorMatrix::IDENTITY.to_gpu(),
+        }
+    }
+}
+
+impl ImageInfo {
+    pub fn yuv(format: YuvFormat, color_space: YuvColorSpace, range: YuvRange) -> Self {
+        Self {
+            yuv: match format {
+                YuvFormat::Nv12 => 1,
+                YuvFormat::I420 => 2,
+            },
+            matrix: yuv_to_rgb(color_space, range).to_gpu(),
+            ..Default::default()
+        }
+    }
+}
+
+/// An entry in the image table. The generation is bumped when the
+/// image is deleted so stale `ImageIndex`es can be detected once the
+/// slot is reused.
+#[derive(Default)]
+pub(crate) struct ImageSlot {
+    pub generation: u32,
+    pub image: Option<Image>,
+}
+
+/// GPU resources for an image.
+pub(crate) struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub format: ImageFormat,
+    pub options: SamplerOptions,
+    pub storage: ImageStorage,
+}
+
+impl Image {
+    pub fn create_texture(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: ImageFormat,
+        options: &SamplerOptions,
+    ) -> wgpu::Texture {
+        let texture_format = format.texture_format();
+
+        let (mip_level_count, usage) = if options.mipmaps {
+            (
+                MipmapGenerator::mip_level_count(width, height),
+                wgpu::TextureUsages::RENDER_ATTACHMENT,
+            )
+        } else {
+            (1, wgpu::TextureUsages::empty())
+        };
+
+        device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: texture_format,
+            usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING | usage,
+            label: Some("lyte image"),
+            view_formats: &[texture_format],
+        })
+    }
+
+    /// Writes validated pixel data to a region of the image, given
+    /// the image atlas texture for atlased images.
+    pub fn write(
+        &self,
+        queue: &wgpu::Queue,
+        atlas_texture: Option<&wgpu::Texture>,
+        origin: [u32; 2],
+        size: [u32; 2],
+        data: &[u8],
+        bytes_per_row: u32,
+    ) {
+        let (texture, offset) = match &self.storage {
+            ImageStorage::Texture { texture, .. } => (texture, [0, 0]),
+            ImageStorage::Atlas { origin, .. } => (
+                atlas_texture.expect("atlased image without an atlas"),
+                *origin,
+            ),
+            ImageStorage::External { .. } => unreachable!("writing to an external texture"),
+            ImageStorage::Yuv { .. } => unreachable!("writing to a YUV image as one plane"),
+        };
+
+        write_texture(
+            queue,
+            texture,
+            [offset[0] + origin[0], offset[1] + origin[1]],
+            size,
+            data,
+            bytes_per_row,
+        );
+    }
+}
+
+/// Writes validated pixel data to a region of a texture.
+pub(crate) fn write_texture(
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    origin: [u32; 2],
+    size: [u32; 2],
+    data: &[u8],
+    bytes_per_row: u32,
+) {
+    // Unlike buffer copies, write_texture doesn't require rows
+    // padded to COPY_BYTES_PER_ROW_ALIGNMENT.
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            aspect: wgpu::TextureAspect::All,
+            origin: wgpu::Origin3d {
+                x: origin[0],
+                y: origin[1],
+                z: 0,
+            },
+        },
+        data,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(bytes_per_row),
+            rows_per_image: Some(size[1]),
+        },
+        wgpu::Extent3d {
+            width: size[0],
+            height: size[1],
+            depth_or_array_layers: 1,
+        },
+    );
+}
+
+/// Checks that a rectangle lies within an image.
+pub(crate) fn check_bounds(rect: ImageRect, width: u32, height: u32) -> Result<(), ImageError> {
+    let fits =
+        |origin: u32, size: u32, max: u32| origin.checked_add(size).is_some_and(|end| end <= max);
+    if !fits(rect.origin.x, rect.size.width, width)
+        || !fits(rect.origin.y, rect.size.height, height)
+    {
+        return Err(ImageError::OutOfBounds {
+            x: rect.origin.x,
+            y: rect.origin.y,
+            width: rect.size.width,
+            height: rect.size.height,
+        });
+    }
+    Ok(())
+}
+
+/// Checks pixel data against the image dimensions and returns the
+/// source stride in bytes.
+pub(crate) fn validate_pixels(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    format: ImageFormat,
+    bytes_per_row: Option<u32>,
+    max_dimension: u32,
+) -> Result<u32, ImageError> {
+    if width == 0 || height == 0 || width > max_dimension || height > max_dimension {
+        return Err(ImageError::InvalidSize {
+            width,
+            height,
+            max: max_dimension,
+        });
+    }
+
+    let bpp = format.bytes_per_pixel();
+    let min = width * bpp;
+    let stride = bytes_per_row.unwrap_or(min);
+    if stride < min || !stride.is_multiple_of(bpp) {
+        return Err(ImageError::InvalidStride {
+            bytes_per_row: stride,
+            min,
+        });
+    }
+
+    let expected = stride as usize * (height as usize - 1) + min as usize;
+    if data.len() < expected {
+        return Err(ImageError::DataTooShort {
+            len: data.len(),
+            expected,
+        });
+    }
+
+    Ok(stride)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_image_fit() {
+        let src = LocalSize::new(200.0, 100.0);
+        let dst = LocalRect::new(LocalPoint::new(10.0, 10.0), LocalSize::new(100.0, 100.0));
+
+        assert_eq!(ImageFit::Fill.place(src, dst), dst);
+        assert_eq!(
+            ImageFit::Contain.place(src, dst),
+            LocalRect::new(LocalPoint::new(10.0, 35.0), LocalSize::new(100.0, 50.0))
+        );
+        assert_eq!(
+            ImageFit::Cover.place(src, dst),
+            LocalRect::new(LocalPoint::new(-40.0, 10.0), LocalSize::new(200.0, 100.0))
+        );
+        assert_eq!(
+            ImageFit::None.place(src, dst),
+            LocalRect::new(LocalPoint::new(-40.0, 10.0), LocalSize::new(200.0, 100.0))
+        );
+        assert_eq!(
+            ImageFit::None.place(LocalSize::new(20.0, 10.0), dst),
+            LocalRect::new(LocalPoint::new(50.0, 55.0), LocalSize::new(20.0, 10.0))
+        );
+    }
+
+    #[test]
+    fn test_validate_pixels() {
+        let data = vec![0_u8; 64];
+
+        assert_eq!(
+            validate_pixels(&data, 4, 4, ImageFormat::Rgba8Srgb, None, 8192),
+            Ok(16)
+        );
+
+        // Padded rows, last row needn't be padded.
+        assert_eq!(
+            validate_pixels(&data[..60], 3, 4, ImageFormat::Rgba8, Some(16), 8192),
+            Ok(16)
+        );
+
+        assert_eq!(
+            validate_pixels(&data, 0, 4, ImageFormat::R8, None, 8192),
+            Err(ImageError::InvalidSize {
+                width: 0,
+                height: 4,
+                max: 8192
+            })
+        );
+
+        assert_eq!(
+            validate_pixels(&data, 4, 4, ImageFormat::R8, None, 2),
+            Err(ImageError::InvalidSize {
+                width: 4,
+                height: 4,
+                max: 2
+            })
+        );
+
+        assert_eq!(
+            validate_pixels(&data, 4, 2, ImageFormat::Rg8, Some(6), 8192),
+            Err(ImageError::InvalidStride {
+                bytes_per_row: 6,
+                min: 8
+            })
+        );
+
+        assert_eq!(
+            validate_pixels(&data, 4, 2, ImageFormat::Rg8, Some(9), 8192),
+            Err(ImageError::InvalidStride {
+                bytes_per_row: 9,
+                min: 8
+            })
+        );
+
+        assert_eq!(
+            validate_pixels(&data, 4, 4, ImageFormat::Rgba16F, None, 8192),
+            Err(ImageError::DataTooShort {
+                len: 64,
+                expected: 128
+            })
+        );
+    }
+
+    #[test]
+    fn test_yuv_to_rgb() {
+        let rgb = |m: &ColorMatrix, y: f32, u: f32, v: f32| {
+            let c = m.apply(Color::new(y / 255.0, u / 255.0, v / 255.0, 1.0));
+            [c.r, c.g, c.b, c.a].map(|x| (x * 255.0).round())
+        };
+
+        // Black, white and mid gray have no chroma in any color space.
+        for color_space in [YuvColorSpace::Bt601, YuvColorSpace::Bt709] {
+            let limited = yuv_to_rgb(color_space, YuvRange::Limited);
+            assert_eq!(rgb(&limited, 16.0, 128.0, 128.0), [0.0, 0.0, 0.0, 255.0]);
+            assert_eq!(rgb(&limited, 235.0, 128.0, 128.0), [255.0; 4]);
+
+            let full = yuv_to_rgb(color_space, YuvRange::Full);
+            assert_eq!(rgb(&full, 0.0, 128.0, 128.0), [0.0, 0.0, 0.0, 255.0]);
+            assert_eq!(
+                rgb(&full, 128.0, 128.0, 128.0),
+                [128.0, 128.0, 128.0, 255.0]
+            );
+        }
+
+        // BT.601 full range red, as JPEG encodes it.
+        let full = yuv_to_rgb(YuvColorSpace::Bt601, YuvRange::Full);
+        assert_eq!(rgb(&full, 76.0, 85.0, 255.0), [254.0, 0.0, 0.0, 255.0]);
+    }
+}
@@ -0,0 +1,277 @@
+use crate::gpu_vec::GPUVec;
+use crate::image::{ImageFilter, ImageInfo, ImageSlot, ImageStorage, ImageWrap, SamplerOptions};
+
+/// Device features needed to bind all images at once in a binding
+/// array. Request these when creating the device to let `Vger::encode`
+/// draw each frame without splitting draws at image changes. The array
+/// is used whenever they're available, unless turned off with
+/// `Vger::set_image_array`.
+pub const IMAGE_ARRAY_FEATURES: wgpu::Features = wgpu::Features::TEXTURE_BINDING_ARRAY
+    .union(wgpu::Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING);
+
+/// Binds every image, along with the image atlas, in texture and
+/// sampler binding arrays indexed by `paint.image`. See
+/// image_array.wgsl for the layout of the arrays.
+pub struct ImageArray {
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    samplers: Vec<wgpu::Sampler>,
+    infos: GPUVec<ImageInfo>,
+    capacity: usize,
+    bind_group: Option<wgpu::BindGroup>,
+    dirty: bool,
+}
+
+impl ImageArray {
+    /// Upper limit on the number of textures in the array.
+    pub const MAX_TEXTURES: usize = 1024;
+
+    /// One sampler for each filter and wrap mode. Mipmapped images use
+    /// the same samplers, since a sampler reads every level a texture
+    /// has.
+    const SAMPLER_COUNT: usize = 6;
+
+    /// Returns `None` if the device lacks `IMAGE_ARRAY_FEATURES`.
+    pub fn new(device: &wgpu::Device) -> Option<Self> {
+        if !device.features().contains(IMAGE_ARRAY_FEATURES) {
+            return None;
+        }
+
+        // The glyph atlas and its sampler count against the same
+        // per-stage limits.
+        let limits = device.limits();
+        let capacity =
+            (limits.max_sampled_textures_per_shader_stage as usize - 1).min(Self::MAX_TEXTURES);
+        if capacity < 4 || (limits.max_samplers_per_shader_stage as usize) < Self::SAMPLER_COUNT + 1
+        {
+            return None;
+        }
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: std::num::NonZeroU32::new(capacity as u32),
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: std::num::NonZeroU32::new(Self::SAMPLER_COUNT as u32),
+                },
+                GPUVec::<ImageInfo>::bind_group_layout_entry(2),
+            ],
+            label: Some("image_array_bind_group_layout"),
+        });
+
+        let mut samplers = Vec::with_capacity(Self::SAMPLER_COUNT);
+        for filter in [ImageFilter::Nearest, ImageFilter::Linear] {
+            for wrap in [ImageWrap::Clamp, ImageWrap::Repeat, ImageWrap::Mirror] {
+                let options = SamplerOptions {
+                    filter,
+                    wrap,
+                    mipmaps: false,
+                };
+                debug_assert_eq!(Self::sampler_index(&options), samplers.len() as u32);
+                samplers.push(options.create_sampler(device));
+            }
+        }
+
+        Some(Self {
+            bind_group_layout,
+            samplers,
+            infos: GPUVec::new(device, capacity, "image infos"),
+            capacity,
+            bind_group: None,
+            dirty: true,
+        })
+    }
+
+    fn sampler_index(options: &SamplerOptions) -> u32 {
+        let filter = match options.filter {
+            ImageFilter::Nearest => 0,
+            ImageFilter::Linear => 1,
+        };
+        let wrap = match options.wrap {
+            ImageWrap::Clamp => 0,
+            ImageWrap::Repeat => 1,
+            ImageWrap::Mirror => 2,
+        };
+        filter * 3 + wrap
+    }
+
+    /// Marks the bind group as out of date. Call when images are
+    /// created, deleted or reallocated.
+    pub fn invalidate(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Returns the bind group for the current images, rebuilding it if
+    /// they changed. Returns `None` if the images can't all be bound at
+    /// once, in which case they must be bound one at a time.
+    pub fn bind_group(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        images: &[ImageSlot],
+        atlas_texture: Option<&wgpu::Texture>,
+        empty_view: &wgpu::TextureView,
+    ) -> Option<&wgpu::BindGroup> {
+        if self.dirty {
+            self.dirty = false;
+            self.bind_group =
+                self.create_bind_group(device, queue, images, atlas_texture, empty_view);
+        }
+        self.bind_group.as_ref()
+    }
+
+    fn create_bind_group(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        images: &[ImageSlot],
+        atlas_texture: Option<&wgpu::Texture>,
+        empty_view: &wgpu::TextureView,
+    ) -> Option<wgpu::BindGroup> {
+        let default_view = wgpu::TextureViewDescriptor::default();
+        let default_sampler = Self::sampler_index(&SamplerOptions::default());
+
+        // Elements without a view use empty_view.
+        let mut views = vec![atlas_texture.map(|texture| texture.create_view(&default_view))];
+        let mut chroma_views = vec![];
+
+        self.infos.clear();
+        self.infos.push(ImageInfo {
+            sampler_index: default_sampler,
+            ..Default::default()
+        });
+
+        for slot in images {
+            let mut info = ImageInfo {
+                generation: slot.generation,
+                sampler_index: default_sampler,
+                ..Default::default()
+            };
+
+            let view = match &slot.image {
+                Some(image) => {
+                    info.sampler_index = Self::sampler_index(&image.options);
+                    match &image.storage {
+                        ImageStorage::Texture { texture, .. } => {
+                            Some(texture.create_view(&default_view))
+                        }
+                        // Paints of atlased images use the atlas.
+                        ImageStorage::Atlas { .. } => None,
+                        // Only the caller has the view.
+                        ImageStorage::External { .. } => return None,
+                        ImageStorage::Yuv {
+                            info: yuv_info,
+                            planes,
+                            ..
+                        } => {
+                            info.yuv = yuv_info.yuv;
+                            info.matrix = yuv_info.matrix;
+                            info.chroma = (1 + images.len() + chroma_views.len()) as u32;
+                            chroma_views.extend(
+                                planes[1..]
+                                    .iter()
+                                    .map(|plane| plane.create_view(&default_view)),
+                            );
+                            Some(planes[0].create_view(&default_view))
+                        }
+                    }
+                }
+                None => None,
+            };
+
+            views.push(view);
+            self.infos.push(info);
+        }
+
+        views.extend(chroma_views.into_iter().map(Some));
+        if views.len() > self.capacity {
+            return None;
+        }
+
+        // Without PARTIALLY_BOUND_BINDING_ARRAY every element must be
+        // bound.
+        let mut view_refs: Vec<&wgpu::TextureView> = views
+            .iter()
+            .map(|view| view.as_ref().unwrap_or(empty_view))
+            .collect();
+        view_refs.resize(self.capacity, empty_view);
+
+        let sampler_refs: Vec<&wgpu::Sampler> = self.samplers.iter().collect();
+
+        self.infos.update(device, queue);
+
+        Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureViewArray(&view_refs),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::SamplerArray(&sampler_refs),
+                },
+                self.infos.bind_group_entry(2),
+            ],
+            label: Some("vger image array bind group"),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn validate(source: &str, capabilities: naga::valid::Capabilities) {
+        let module = naga::front::wgsl::parse_str(source).unwrap();
+        naga::valid::Validator::new(naga::valid::ValidationFlags::all(), capabilities)
+            .validate(&module)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_shader_variants() {
+        use naga::valid::Capabilities;
+
+        // The test device can't create the image array pipeline, so
+        // check the shader validates with the capabilities wgpu grants
+        // for IMAGE_ARRAY_FEATURES.
+        validate(crate::SHADER_SOURCE, Capabilities::empty());
+        validate(
+            crate::IMAGE_ARRAY_SHADER_SOURCE,
+            Capabilities::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING
+                | Capabilities::SAMPLER_NON_UNIFORM_INDEXING,
+        );
+    }
+
+    #[test]
+    fn test_sampler_index() {
+        let mut seen = [false; ImageArray::SAMPLER_COUNT];
+        for filter in [ImageFilter::Nearest, ImageFilter::Linear] {
+            for wrap in [ImageWrap::Clamp, ImageWrap::Repeat, ImageWrap::Mirror] {
+                let index = |mipmaps| {
+                    ImageArray::sampler_index(&SamplerOptions {
+                        filter,
+                        wrap,
+                        mipmaps,
+                    })
+                };
+                assert_eq!(index(false), index(true));
+                assert!(!seen[index(false) as usize]);
+                seen[index(false) as usize] = true;
+            }
+        }
+        assert!(seen.iter().all(|&seen| seen));
+    }
+}
@@ -0,0 +1,52 @@
+// Image bindings when every image is bound at once, see ImageArray.
+// Element 0 of the arrays is the image atlas and element i + 1 is
+// image i. Chroma planes of YUV images come after the images.
+
+@group(2)
+@binding(0)
+var textures : binding_array<texture_2d<f32>>;
+
+@group(2)
+@binding(1)
+var samplers : binding_array<sampler>;
+
+struct ImageInfos {
+    infos: array<ImageInfo>,
+};
+
+@group(2)
+@binding(2)
+var<storage> image_infos: ImageInfos;
+
+// Atlased images (-2) use the atlas, as does no image (-1).
+fn imageElement(paint: Paint) -> u32 {
+    return u32(max(paint.image + 1, 0));
+}
+
+fn imageDimensions(paint: Paint) -> vec2<f32> {
+    return vec2<f32>(textureDimensions(textures[imageElement(paint)]));
+}
+
+// Samples the paint's image, converting YUV images to RGB.
+fn sampleImage(paint: Paint, t: vec2<f32>) -> vec4<f32> {
+    let element = imageElement(paint);
+    let info = image_infos.infos[element];
+    let y = textureSample(textures[element], samplers[info.sampler_index], t);
+
+    // The image was deleted after the paint was created. Draw nothing.
+    if paint.image >= 0 && info.generation != paint.image_generation {
+        return vec4<f32>(0.0);
+    }
+
+    if info.yuv == 0u {
+        return y;
+    }
+
+    // Chroma planes have no mips, so level 0 is all there is.
+    let u = textureSampleLevel(textures[info.chroma], samplers[info.sampler_index], t, 0.0);
+    var yuv = vec4<f32>(y.r, u.rg, 1.0);
+    if info.yuv == 2u {
+        yuv.z = textureSampleLevel(textures[info.chroma + 1u], samplers[info.sampler_index], t, 0.0).r;
+    }
+    return yuvToRgb(info, yuv);
+}
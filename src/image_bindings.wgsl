@@ -0,0 +1,41 @@
+// Image bindings when each image has a bind group of its own.
+
+@group(2)
+@binding(0)
+var tex : texture_2d<f32>;
+
+@group(2)
+@binding(1)
+var image_samp : sampler;
+
+// Chroma planes of YUV images. NV12 uses tex_u for both U and V.
+@group(2)
+@binding(2)
+var tex_u : texture_2d<f32>;
+
+@group(2)
+@binding(3)
+var tex_v : texture_2d<f32>;
+
+@group(2)
+@binding(4)
+var<uniform> image_info: ImageInfo;
+
+fn imageDimensions(paint: Paint) -> vec2<f32> {
+    return vec2<f32>(textureDimensions(tex));
+}
+
+// Samples the paint's image, converting YUV images to RGB.
+fn sampleImage(paint: Paint, t: vec2<f32>) -> vec4<f32> {
+    let y = textureSample(tex, image_samp, t);
+    if image_info.yuv == 0u {
+        return y;
+    }
+
+    // Chroma planes have no mips, so level 0 is all there is.
+    var yuv = vec4<f32>(y.r, textureSampleLevel(tex_u, image_samp, t, 0.0).rg, 1.0);
+    if image_info.yuv == 2u {
+        yuv.z = textureSampleLevel(tex_v, image_samp, t, 0.0).r;
+    }
+    return yuvToRgb(image_info, yuv);
+}
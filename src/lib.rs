@@ -1,4 +1,4 @@
-use fontdue::layout::{CoordinateSystem, Layout, LayoutSettings, TextStyle};
+use fontdue::layout::{CoordinateSystem, GlyphPosition, GlyphRasterConfig, Layout, TextStyle};
 use std::sync::Arc;
 
 mod path;
@@ -20,14 +20,42 @@ mod gpu_vec;
 use gpu_vec::*;
 
 pub mod color;
-pub use color::Color;
+pub use color::{Color, ColorMatrix};
 
 pub mod atlas;
+use atlas::ImageAtlas;
+
+pub mod image;
+use image::{Image, ImageInfo, ImageSlot, ImageStorage, ATLAS_IMAGE};
+pub use image::{
+    ImageAdjustments, ImageError, ImageFilter, ImageFit, ImageFormat, ImageWrap, SamplerOptions,
+    SliceMode, YuvColorSpace, YuvFormat, YuvPlane, YuvRange,
+};
+
+mod mipmap;
+use mipmap::MipmapGenerator;
+
+mod image_array;
+use image_array::ImageArray;
+pub use image_array::IMAGE_ARRAY_FEATURES;
+
+#[cfg(feature = "image-decode")]
+mod decode;
+
+mod attributed;
+pub use attributed::{AttributedText, TextFill, TextSpan};
+
+mod text_layout;
+pub use text_layout::{HorizontalAlign, TextLayoutOptions, VerticalAlign};
 
 mod glyphs;
-use glyphs::GlyphCache;
+pub use glyphs::FontError;
+use glyphs::{snap_pen, GlyphCache};
 
-use wgpu::util::DeviceExt;
+#[cfg(feature = "shaping")]
+mod shaping;
+#[cfg(feature = "shaping")]
+pub use shaping::ShapedGlyph;
 
 #[allow(dead_code)]
 #[derive(Copy, Clone, Debug)]
@@ -40,15 +68,51 @@ pub struct PaintIndex {
     index: usize,
 }
 
-#[derive(Copy, Clone, Debug)]
+/// Handle to an image. Handles to deleted images are detected even
+/// after their slot is reused by a new image.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct ImageIndex {
     index: usize,
+    generation: u32,
+}
+
+/// Handle to a font added with `Vger::add_font`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FontId {
+    index: usize,
+}
+
+impl FontId {
+    /// The built-in font.
+    pub const DEFAULT: FontId = FontId { index: 0 };
+
+    /// Atkinson Hyperlegible, built in as the fallback for `DEFAULT`.
+    pub const FALLBACK: FontId = FontId { index: 1 };
+}
+
+impl Default for FontId {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// A glyph to render with `Vger::render_glyphs`.
+struct GlyphPen {
+    font: usize,
+    key: GlyphRasterConfig,
+
+    /// Pen position on the baseline, in device pixels.
+    pen: [f32; 2],
+    paint: PaintIndex,
 }
 
 #[derive(Copy, Clone, Debug)]
 pub struct LineMetrics {
     pub glyph_start: usize,
+
+    /// Index of the line's last glyph, which is in the line.
     pub glyph_end: usize,
+
     pub bounds: LocalRect,
 }
 
@@ -74,6 +138,18 @@ impl Scissor {
     }
 }
 
+/// The shader, with images bound one at a time.
+const SHADER_SOURCE: &str = concat!(
+    include_str!("shader.wgsl"),
+    include_str!("image_bindings.wgsl")
+);
+
+/// The shader, with all images bound at once by `ImageArray`.
+const IMAGE_ARRAY_SHADER_SOURCE: &str = concat!(
+    include_str!("shader.wgsl"),
+    include_str!("image_array.wgsl")
+);
+
 pub struct Vger {
     device: Arc<wgpu::Device>,
     queue: Arc<wgpu::Queue>,
@@ -93,11 +169,27 @@ pub struct Vger {
     path_scanner: PathScanner,
     pen: LocalPoint,
     pub glyph_cache: GlyphCache,
-    layout: Layout,
-    images: Vec<Option<wgpu::Texture>>,
-    image_bind_groups: Vec<Option<wgpu::BindGroup>>,
+    /// Glyph user data is the index of the glyph's span.
+    layout: Layout<usize>,
+
+    /// Glyphs from `layout`, after justification, with the bounds of
+    /// their quads.
+    glyphs: Vec<GlyphPosition<usize>>,
+
+    /// Pen positions of `glyphs` in device pixels.
+    pens: Vec<[f32; 2]>,
+    images: Vec<ImageSlot>,
+    free_images: Vec<usize>,
     image_bind_group_layout: wgpu::BindGroupLayout,
     default_image_bind_group: wgpu::BindGroup,
+    empty_view: wgpu::TextureView,
+    image_info: GPUVec<ImageInfo>,
+    mipmaps: MipmapGenerator,
+    image_atlas: Option<(ImageAtlas, wgpu::BindGroup)>,
+    atlas_images: bool,
+    image_array: Option<(ImageArray, wgpu::RenderPipeline)>,
+    use_image_array: bool,
+    dither_gradients: bool,
 }
 
 impl Vger {
@@ -107,13 +199,6 @@ impl Vger {
         queue: Arc<wgpu::Queue>,
         texture_format: wgpu::TextureFormat,
     ) -> Self {
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: None,
-            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!(
-                "shader.wgsl"
-            ))),
-        });
-
         let scenes = [
             Scene::new(&device),
             Scene::new(&device),
@@ -155,16 +240,55 @@ impl Vger {
 
         let image_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        multisampled: false,
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        view_dimension: wgpu::TextureViewDimension::D2,
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
                     },
-                    count: None,
-                }],
+                    // Chroma planes of YUV images.
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
                 label: Some("image_bind_group_layout"),
             });
 
@@ -197,22 +321,129 @@ impl Vger {
             label: Some("vger bind group"),
         });
 
+        // Bound when no image is in use, or the paint's image was
+        // deleted. Textures start out zeroed, so this is a single
+        // transparent pixel.
+        let empty_texture = Image::create_texture(
+            &device,
+            1,
+            1,
+            ImageFormat::Rgba8,
+            &SamplerOptions::default(),
+        );
+        let empty_view = empty_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // Shared by all RGBA images.
+        let mut image_info = GPUVec::new_uniforms(&device, "image info");
+        image_info.push(ImageInfo::default());
+        image_info.update(&device, &queue);
+
         let default_image_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &image_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: wgpu::BindingResource::TextureView(&texture_view),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&empty_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&glyph_sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&empty_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&empty_view),
+                },
+                image_info.bind_group_entry(4),
+            ],
             label: Some("vger default image bind group"),
         });
 
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: None,
-            bind_group_layouts: &[
-                &Scene::bind_group_layout(&device),
+        let scene_bind_group_layout = Scene::bind_group_layout(&device);
+
+        let pipeline = Self::create_pipeline(
+            &device,
+            SHADER_SOURCE,
+            &[
+                &scene_bind_group_layout,
                 &uniform_bind_group_layout,
                 &image_bind_group_layout,
             ],
+            texture_format,
+        );
+
+        let image_array = ImageArray::new(&device).map(|array| {
+            let pipeline = Self::create_pipeline(
+                &device,
+                IMAGE_ARRAY_SHADER_SOURCE,
+                &[
+                    &scene_bind_group_layout,
+                    &uniform_bind_group_layout,
+                    &array.bind_group_layout,
+                ],
+                texture_format,
+            );
+            (array, pipeline)
+        });
+
+        let layout = Layout::new(CoordinateSystem::PositiveYUp);
+
+        let mipmaps = MipmapGenerator::new(&device);
+
+        Self {
+            device,
+            queue,
+            scenes,
+            cur_scene: 0,
+            cur_layer: 0,
+            tx_stack: vec![],
+            scissor_stack: vec![],
+            device_px_ratio: 1.0,
+            screen_size: ScreenSize::new(512.0, 512.0),
+            paint_count: 0,
+            pipeline,
+            uniforms,
+            uniform_bind_group,
+            xform_count: 0,
+            scissor_count: 0,
+            path_scanner: PathScanner::new(),
+            pen: LocalPoint::zero(),
+            glyph_cache,
+            layout,
+            glyphs: vec![],
+            pens: vec![],
+            images: vec![],
+            free_images: vec![],
+            image_bind_group_layout,
+            default_image_bind_group,
+            empty_view,
+            image_info,
+            mipmaps,
+            image_atlas: None,
+            atlas_images: false,
+            use_image_array: image_array.is_some(),
+            image_array,
+            dither_gradients: false,
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        source: &str,
+        bind_group_layouts: &[&wgpu::BindGroupLayout],
+        texture_format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(source)),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts,
             push_constant_ranges: &[],
         });
 
@@ -222,7 +453,7 @@ impl Vger {
             dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
         };
 
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: None,
             layout: Some(&pipeline_layout),
             vertex: wgpu::VertexState {
@@ -253,35 +484,7 @@ impl Vger {
             multisample: wgpu::MultisampleState::default(),
             multiview: None,
             cache: None,
-        });
-
-        let layout = Layout::new(CoordinateSystem::PositiveYUp);
-
-        Self {
-            device,
-            queue,
-            scenes,
-            cur_scene: 0,
-            cur_layer: 0,
-            tx_stack: vec![],
-            scissor_stack: vec![],
-            device_px_ratio: 1.0,
-            screen_size: ScreenSize::new(512.0, 512.0),
-            paint_count: 0,
-            pipeline,
-            uniforms,
-            uniform_bind_group,
-            xform_count: 0,
-            scissor_count: 0,
-            path_scanner: PathScanner::new(),
-            pen: LocalPoint::zero(),
-            glyph_cache,
-            layout,
-            images: vec![],
-            image_bind_groups: vec![],
-            image_bind_group_layout,
-            default_image_bind_group,
-        }
+        })
     }
 
     /// Begin rendering.
@@ -303,6 +506,9 @@ impl Vger {
         self.xform_count = 0;
         self.scissor_count = 0;
         self.pen = LocalPoint::zero();
+        if let Some((atlas, _)) = &mut self.image_atlas {
+            atlas.recycle();
+        }
     }
 
     /// Saves rendering state (transform and scissor rect).
@@ -323,7 +529,6 @@ impl Vger {
         let queue = &self.queue;
         self.scenes[self.cur_scene].update(device, queue);
         self.uniforms.update(device, queue);
-        let mut current_texture = -1;
 
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("vger encoder"),
@@ -331,10 +536,32 @@ impl Vger {
 
         self.glyph_cache.update(device, &mut encoder);
 
+        // With every image bound at once, the whole layer is one draw.
+        let image_array = match &mut self.image_array {
+            Some((array, pipeline)) if self.use_image_array => {
+                let pipeline = &*pipeline;
+                array
+                    .bind_group(
+                        device,
+                        queue,
+                        &self.images,
+                        self.image_atlas
+                            .as_ref()
+                            .map(|(atlas, _)| &atlas.atlas_texture),
+                        &self.empty_view,
+                    )
+                    .map(|bind_group| (bind_group, pipeline))
+            }
+            _ => None,
+        };
+
         {
             let mut rpass = encoder.begin_render_pass(render_pass);
 
-            rpass.set_pipeline(&self.pipeline);
+            rpass.set_pipeline(match image_array {
+                Some((_, pipeline)) => pipeline,
+                None => &self.pipeline,
+            });
 
             rpass.set_bind_group(
                 0,
@@ -343,48 +570,17 @@ impl Vger {
             );
 
             rpass.set_bind_group(1, &self.uniform_bind_group, &[]);
-            rpass.set_bind_group(2, &self.default_image_bind_group, &[]);
 
             let scene = &self.scenes[self.cur_scene];
             let n = scene.prims[self.cur_layer].len();
-            let mut m: u32 = 0;
-            let mut start: u32 = 0;
-
-            for i in 0..n {
-                let prim = &scene.prims[self.cur_layer][i];
-                let image_id = scene.paints[prim.paint as usize].image;
-
-                // Image changed, render.
-                if image_id >= 0 && image_id != current_texture {
-                    // println!("image changed: encoding {:?} prims", m);
-                    if m > 0 {
-                        rpass.draw(
-                            /*vertices*/ 0..4,
-                            /*instances*/ start..(start + m),
-                        );
-                    }
-
-                    current_texture = image_id;
-                    rpass.set_bind_group(
-                        2,
-                        self.image_bind_groups[image_id as usize].as_ref().unwrap(),
-                        &[],
-                    );
 
-                    start += m;
-                    m = 0;
+            if let Some((bind_group, _)) = image_array {
+                rpass.set_bind_group(2, bind_group, &[]);
+                if n > 0 {
+                    rpass.draw(/*vertices*/ 0..4, /*instances*/ 0..n as u32);
                 }
-
-                m += 1;
-            }
-
-            // println!("encoding {:?} prims", m);
-
-            if m > 0 {
-                rpass.draw(
-                    /*vertices*/ 0..4,
-                    /*instances*/ start..(start + m),
-                )
+            } else {
+                self.encode_image_draws(&mut rpass, n);
             }
         }
         queue.submit(Some(encoder.finish()));
@@ -398,6 +594,68 @@ impl Vger {
         }
     }
 
+    /// Draws the current layer, splitting draws wherever the image
+    /// changes so each image's bind group can be set.
+    fn encode_image_draws<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>, n: usize) {
+        rpass.set_bind_group(2, &self.default_image_bind_group, &[]);
+
+        let scene = &self.scenes[self.cur_scene];
+        let mut current_texture = (-1, 0);
+        let mut m: u32 = 0;
+        let mut start: u32 = 0;
+
+        for i in 0..n {
+            let prim = &scene.prims[self.cur_layer][i];
+            let paint = &scene.paints[prim.paint as usize];
+            let image_id = paint.image;
+            let image = (image_id, paint.image_generation);
+
+            // Image changed, render.
+            if image_id != -1 && image != current_texture {
+                // println!("image changed: encoding {:?} prims", m);
+                if m > 0 {
+                    rpass.draw(
+                        /*vertices*/ 0..4,
+                        /*instances*/ start..(start + m),
+                    );
+                }
+
+                current_texture = image;
+                let bind_group = if image_id == ATLAS_IMAGE {
+                    &self.image_atlas.as_ref().unwrap().1
+                } else {
+                    let index = ImageIndex {
+                        index: image_id as usize,
+                        generation: paint.image_generation,
+                    };
+                    match self.image(index).map(|img| &img.storage) {
+                        Some(ImageStorage::Texture { bind_group, .. })
+                        | Some(ImageStorage::External { bind_group })
+                        | Some(ImageStorage::Yuv { bind_group, .. }) => bind_group,
+                        // The image was deleted after the paint was
+                        // created. Draw nothing.
+                        _ => &self.default_image_bind_group,
+                    }
+                };
+                rpass.set_bind_group(2, bind_group, &[]);
+
+                start += m;
+                m = 0;
+            }
+
+            m += 1;
+        }
+
+        // println!("encoding {:?} prims", m);
+
+        if m > 0 {
+            rpass.draw(
+                /*vertices*/ 0..4,
+                /*instances*/ start..(start + m),
+            )
+        }
+    }
+
     fn render(&mut self, prim: Prim) {
         self.scenes[self.cur_scene].prims[self.cur_layer].push(prim);
     }
@@ -612,6 +870,100 @@ impl Vger {
         self.render(prim);
     }
 
+    /// Draws the src_rect region of an image (in pixels, or the whole
+    /// image if `None`) into dst_rect, fitted according to `fit`. The
+    /// drawn area is clipped to dst_rect and has rounded corners of the
+    /// given radius.
+    pub fn draw_image<Rect: Into<LocalRect>>(
+        &mut self,
+        image: ImageIndex,
+        src_rect: Option<ImageRect>,
+        dst_rect: Rect,
+        radius: f32,
+        fit: ImageFit,
+        alpha: f32,
+    ) -> Result<(), ImageError> {
+        let img = self.image(image).ok_or(ImageError::InvalidImage)?;
+        let image_size = LocalSize::new(img.width as f32, img.height as f32);
+        let src = src_rect.map_or(LocalRect::from_size(image_size), |r| r.cast().cast_unit());
+
+        if src.is_empty() {
+            return Ok(());
+        }
+
+        let dst: LocalRect = dst_rect.into();
+        let placed = fit.place(src.size, dst);
+
+        // Where the whole image lands so that src maps onto placed. Image
+        // rows go down from the top, local space is y-up.
+        let scale = placed.size.to_vector().component_div(src.size.to_vector());
+        let size = LocalSize::new(image_size.width * scale.x, image_size.height * scale.y);
+        let top_left = LocalPoint::new(
+            placed.min_x() - src.min_x() * scale.x,
+            placed.max_y() + src.min_y() * scale.y,
+        );
+        let origin = LocalPoint::new(top_left.x, top_left.y - size.height);
+
+        if let Some(rect) = placed.intersection(&dst) {
+            let paint = self.image_pattern(origin, size, 0.0, image, alpha);
+            self.fill_rect(rect, radius, paint);
+        }
+
+        Ok(())
+    }
+
+    /// Fills dst_rect with a paint masked by an image stretched over
+    /// dst_rect. See `mask_paint`.
+    pub fn draw_mask<Rect: Into<LocalRect>>(
+        &mut self,
+        mask: ImageIndex,
+        dst_rect: Rect,
+        paint: PaintIndex,
+    ) -> Result<(), ImageError> {
+        let dst: LocalRect = dst_rect.into();
+        let paint = self.mask_paint(paint, mask, dst.origin, dst.size, 0.0)?;
+        self.fill_rect(dst, 0.0, paint);
+
+        Ok(())
+    }
+
+    /// Draws a nine-slice (nine-patch) image into dst_rect. The corners
+    /// given by insets stay at one local unit per pixel, while the edges
+    /// and center stretch or tile according to mode.
+    pub fn draw_image_nine_slice<Rect: Into<LocalRect>>(
+        &mut self,
+        image: ImageIndex,
+        insets: ImageInsets,
+        dst_rect: Rect,
+        mode: SliceMode,
+    ) -> Result<(), ImageError> {
+        let img = self.image(image).ok_or(ImageError::InvalidImage)?;
+
+        let (width, height) = (img.width, img.height);
+        if insets.horizontal() > width || insets.vertical() > height {
+            return Err(ImageError::InvalidInsets);
+        }
+
+        let dst: LocalRect = dst_rect.into();
+        let paint = Paint::nine_slice(
+            dst,
+            LocalSize::new(width as f32, height as f32),
+            [
+                insets.left as f32,
+                insets.top as f32,
+                insets.right as f32,
+                insets.bottom as f32,
+            ],
+            mode == SliceMode::Tile,
+            image.index as i32,
+            1.0,
+        );
+        let paint = self.add_paint(self.resolve_image(paint, image));
+        self.fill_rect(dst, 0.0, paint);
+
+        Ok(())
+    }
+
     /// Move the pen to a point (path fills only)
     pub fn move_to<Pt: Into<LocalPoint>>(&mut self, p: Pt) {
         self.pen = p.into();
@@ -646,8 +998,8 @@ impl Vger {
             prim.start = self.scenes[self.cur_scene].cvs.len() as u32;
 
             let mut x_interval = Interval {
-                a: std::f32::MAX,
-                b: std::f32::MIN,
+                a: f32::MAX,
+                b: f32::MIN,
             };
 
             let mut index = self.path_scanner.first;
@@ -675,83 +1027,404 @@ impl Vger {
         self.path_scanner.segments.clear()
     }
 
-    fn setup_layout(&mut self, text: &str, size: u32, max_width: Option<f32>) {
-        let scale = self.device_px_ratio;
+    /// Adds a TrueType or OpenType font, for use with the text
+    /// functions. Fonts live as long as the `Vger`.
+    pub fn add_font(&mut self, bytes: &[u8]) -> Result<FontId, FontError> {
+        let index = self.glyph_cache.add_font(bytes)?;
+        Ok(FontId { index })
+    }
 
-        self.layout.reset(&LayoutSettings {
-            max_width: max_width.map(|w| w * scale),
-            ..LayoutSettings::default()
-        });
+    /// Sets the fonts tried, in order, for characters `font` has no
+    /// glyph for. Characters no font has use `font`'s missing glyph.
+    /// `FontId::DEFAULT` falls back to `FontId::FALLBACK` unless
+    /// changed.
+    pub fn set_font_fallbacks(&mut self, font: FontId, fallbacks: &[FontId]) {
+        let font = self.font_index(font);
+        let fallbacks = fallbacks.iter().map(|f| self.font_index(*f)).collect();
+        self.glyph_cache.set_fallbacks(font, fallbacks);
+    }
 
-        let scaled_size = size as f32 * scale;
+    /// Index of a font in the glyph cache. Fonts from another `Vger`
+    /// fall back to the built-in font.
+    fn font_index(&self, font: FontId) -> usize {
+        if font.index < self.glyph_cache.fonts.len() {
+            font.index
+        } else {
+            FontId::DEFAULT.index
+        }
+    }
 
-        self.layout.append(
-            &[&self.glyph_cache.font],
-            &TextStyle::new(text, scaled_size, 0),
-        );
+    fn setup_layout(&mut self, text: &str, font: FontId, size: f32, options: TextLayoutOptions) {
+        self.reset_layout(options);
+        self.append_layout(text, font, size, 0);
+        self.finish_layout(options);
     }
 
-    /// Renders text.
-    pub fn text(&mut self, text: &str, size: u32, color: Color, max_width: Option<f32>) {
-        self.setup_layout(text, size, max_width);
+    fn setup_attributed_layout(&mut self, text: &AttributedText, options: TextLayoutOptions) {
+        self.reset_layout(options);
+        for (i, span) in text.spans.iter().enumerate() {
+            self.append_layout(&span.text, span.font, span.size, i);
+        }
+        self.finish_layout(options);
+    }
 
-        let scale = self.device_px_ratio;
-        let scaled_size = size as f32 * scale;
+    fn reset_layout(&mut self, options: TextLayoutOptions) {
+        self.layout
+            .reset(&options.layout_settings(self.device_px_ratio));
+    }
+
+    /// Copies the laid out glyphs to `glyphs` and places their pens in
+    /// `pens`, justifying them if needed.
+    ///
+    /// The layout only breaks lines and places baselines. It rounds
+    /// each advance up to a whole pixel, so pens are placed by summing
+    /// the unrounded advances instead, and each glyph's bounds are
+    /// replaced by those of its quad.
+    fn finish_layout(&mut self, options: TextLayoutOptions) {
+        self.glyphs.clear();
+        self.glyphs.extend_from_slice(self.layout.glyphs());
+        self.pens.clear();
+        self.pens.resize(self.glyphs.len(), [0.0, 0.0]);
+
+        // Only text with a width is aligned.
+        let align = match (options.max_width, options.horizontal_align) {
+            (None, _) | (_, HorizontalAlign::Left | HorizontalAlign::Justify) => 0.0,
+            (_, HorizontalAlign::Center) => 0.5,
+            (_, HorizontalAlign::Right) => 1.0,
+        };
+        let max_width = options.max_width.unwrap_or(0.0) * self.device_px_ratio;
+
+        let cache = &mut self.glyph_cache;
+        for line in self.layout.lines().into_iter().flatten() {
+            let range = line.glyph_start..=line.glyph_end;
+            let (glyphs, pens) =
+                match (self.glyphs.get_mut(range.clone()), self.pens.get_mut(range)) {
+                    (Some(glyphs), Some(pens)) => (glyphs, pens),
+                    _ => continue,
+                };
+
+            // Layout gives control characters no metrics.
+            let advances: Vec<f32> = glyphs
+                .iter()
+                .map(|glyph| {
+                    if glyph.char_data.is_control() {
+                        0.0
+                    } else {
+                        cache.fonts[glyph.font_index]
+                            .metrics_indexed(glyph.key.glyph_index, glyph.key.px)
+                            .advance_width
+                    }
+                })
+                .collect();
+
+            // Like shaped text, lines are aligned to whole pixels.
+            let width: f32 = advances.iter().sum();
+            let mut x = ((max_width - width) * align).floor();
+
+            for ((glyph, pen), advance) in glyphs.iter_mut().zip(pens).zip(advances) {
+                *pen = [x, line.baseline_y];
+                x += advance;
+
+                if glyph.char_data.is_control() {
+                    glyph.x = pen[0];
+                    glyph.y = pen[1];
+                } else {
+                    let (origin, size) = cache.glyph_rect(glyph.font_index, glyph.key, *pen);
+                    glyph.x = origin[0];
+                    glyph.y = origin[1];
+                    glyph.width = size[0];
+                    glyph.height = size[1];
+                }
+            }
+        }
+
+        if let (HorizontalAlign::Justify, Some(max_width), Some(lines)) = (
+            options.horizontal_align,
+            options.max_width,
+            self.layout.lines(),
+        ) {
+            text_layout::justify(
+                &mut self.glyphs,
+                &mut self.pens,
+                lines,
+                max_width * self.device_px_ratio,
+            );
+        }
+    }
+
+    /// Appends text to the layout, tagging its glyphs with `span`.
+    fn append_layout(&mut self, text: &str, font: FontId, size: f32, span: usize) {
+        let scaled_size = GlyphCache::bucket_size(size * self.device_px_ratio);
+        let font_index = self.font_index(font);
 
+        for (range, run_font) in self.glyph_cache.font_runs(text, font_index) {
+            self.layout.append(
+                &self.glyph_cache.fonts,
+                &TextStyle::with_user_data(&text[range], scaled_size, run_font, span),
+            );
+        }
+    }
+
+    /// Renders text. Sizes are rounded to at most an eighth of a device
+    /// pixel, or 1% for sizes over 16 device pixels, so that text whose
+    /// size changes continuously shares cached glyphs.
+    pub fn text(
+        &mut self,
+        text: &str,
+        font: FontId,
+        size: f32,
+        color: Color,
+        options: TextLayoutOptions,
+    ) {
         let paint = self.color_paint(color);
+        self.text_with_paint(text, font, size, paint, options);
+    }
+
+    /// Renders text filled with a paint. The paint is evaluated in the
+    /// text's local space, so gradients and images span the whole string.
+    pub fn text_with_paint(
+        &mut self,
+        text: &str,
+        font: FontId,
+        size: f32,
+        paint: PaintIndex,
+        options: TextLayoutOptions,
+    ) {
+        self.setup_layout(text, font, size, options);
+        self.render_layout(&[paint]);
+    }
+
+    /// Renders styled text laid out as one paragraph.
+    pub fn attributed_text(&mut self, text: &AttributedText, options: TextLayoutOptions) {
+        let paints: Vec<PaintIndex> = text
+            .spans
+            .iter()
+            .map(|span| match span.fill {
+                TextFill::Color(color) => self.color_paint(color),
+                TextFill::Paint(paint) => paint,
+            })
+            .collect();
+
+        self.setup_attributed_layout(text, options);
+        self.render_layout(&paints);
+    }
+
+    /// Renders the glyphs in the layout with the paints of their spans.
+    fn render_layout(&mut self, paints: &[PaintIndex]) {
+        let mut pens = Vec::with_capacity(self.glyphs.len());
+        for (glyph, pen) in self.glyphs.iter().zip(&self.pens) {
+            if glyph.char_data.is_control() {
+                continue;
+            }
+
+            pens.push(GlyphPen {
+                font: glyph.font_index,
+                key: glyph.key,
+                pen: *pen,
+                paint: paints[glyph.user_data],
+            });
+        }
+
+        self.render_glyphs(&pens);
+    }
+
+    /// Renders glyphs at pen positions in device pixels. When the
+    /// transform is a translation, pens are snapped with `snap_pen`, so
+    /// glyphs keep their fractional spacing to a quarter pixel and
+    /// sharp edges at any origin.
+    fn render_glyphs(&mut self, pens: &[GlyphPen]) {
+        let scale = self.device_px_ratio;
+        let m = *self.tx_stack.last().unwrap();
+        let translation = if m.m11 == 1.0 && m.m12 == 0.0 && m.m21 == 0.0 && m.m22 == 1.0 {
+            Some([m.m31 * scale, m.m32 * scale])
+        } else {
+            None
+        };
+
         let xform = self.add_xform() as u32;
         let scissor = self.add_scissor() as u32;
 
-        let mut prims = vec![];
-        for (i, glyph) in self.layout.glyphs().iter().enumerate() {
-            let c = text.chars().nth(i).unwrap();
-            // println!("glyph {:?}", c);
-            let info = self.glyph_cache.get_glyph(c, scaled_size);
+        for glyph in pens {
+            let (pen, phase) = match translation {
+                Some([tx, ty]) => {
+                    let ([x, y], phase) = snap_pen([tx + glyph.pen[0], ty + glyph.pen[1]]);
+                    ([x - tx, y - ty], phase)
+                }
+                None => (glyph.pen, 0),
+            };
 
+            let info = self.glyph_cache.get_glyph(glyph.font, glyph.key, phase);
             if let Some(rect) = info.rect {
-                let mut prim = Prim::default();
-                prim.prim_type = PrimType::Glyph as u32;
-                prim.xform = xform;
-                prim.scissor = scissor;
-                assert!(glyph.width == rect.width as usize);
-                assert!(glyph.height == rect.height as usize);
-
-                prim.quad_bounds = [
-                    glyph.x / scale,
-                    glyph.y / scale,
-                    (glyph.x + glyph.width as f32) / scale,
-                    (glyph.y + glyph.height as f32) / scale,
-                ];
-                // println!("quad_bounds: {:?}", prim.quad_bounds);
+                let prim = Self::glyph_prim(
+                    &rect,
+                    [
+                        (pen[0] + info.left as f32) / scale,
+                        (pen[1] + info.bottom as f32) / scale,
+                    ],
+                    scale,
+                    xform,
+                    scissor,
+                    glyph.paint,
+                );
+                self.render(prim);
+            }
+        }
+    }
 
-                prim.tex_bounds = [
-                    rect.x as f32,
-                    (rect.y + rect.height) as f32,
-                    (rect.x + rect.width) as f32,
-                    rect.y as f32,
-                ];
-                prim.paint = paint.index as u32;
-                // println!("tex_bounds: {:?}", prim.tex_bounds);
+    /// Makes a prim drawing a glyph from the glyph atlas, with the
+    /// bottom left of its quad at `origin`.
+    fn glyph_prim(
+        rect: &rect_packer::Rect,
+        origin: [f32; 2],
+        scale: f32,
+        xform: u32,
+        scissor: u32,
+        paint: PaintIndex,
+    ) -> Prim {
+        let mut prim = Prim::default();
+        prim.prim_type = PrimType::Glyph as u32;
+        prim.xform = xform;
+        prim.scissor = scissor;
 
-                prims.push(prim);
+        prim.quad_bounds = [
+            origin[0],
+            origin[1],
+            origin[0] + rect.width as f32 / scale,
+            origin[1] + rect.height as f32 / scale,
+        ];
+        // println!("quad_bounds: {:?}", prim.quad_bounds);
+
+        prim.tex_bounds = [
+            rect.x as f32,
+            (rect.y + rect.height) as f32,
+            (rect.x + rect.width) as f32,
+            rect.y as f32,
+        ];
+        prim.paint = paint.index as u32;
+        // println!("tex_bounds: {:?}", prim.tex_bounds);
+
+        prim
+    }
+
+    /// Renders text shaped with the font's OpenType features, which
+    /// apply kerning, ligatures, mark positioning and the joining of
+    /// complex scripts. Mixed left-to-right and right-to-left text is
+    /// drawn in visual order. Each line of the text is laid out as a
+    /// bidi paragraph. Lines aren't wrapped: `options.max_width` is
+    /// only used for alignment.
+    #[cfg(feature = "shaping")]
+    pub fn shaped_text(
+        &mut self,
+        text: &str,
+        font: FontId,
+        size: f32,
+        color: Color,
+        options: TextLayoutOptions,
+    ) {
+        let paint = self.color_paint(color);
+        self.shaped_text_with_paint(text, font, size, paint, options);
+    }
+
+    /// Renders shaped text filled with a paint. See `shaped_text`.
+    #[cfg(feature = "shaping")]
+    pub fn shaped_text_with_paint(
+        &mut self,
+        text: &str,
+        font: FontId,
+        size: f32,
+        paint: PaintIndex,
+        options: TextLayoutOptions,
+    ) {
+        let scale = self.device_px_ratio;
+        let font_index = self.font_index(font);
+        let glyphs = shaping::shape(
+            &self.glyph_cache,
+            text,
+            font_index,
+            GlyphCache::bucket_size(size * scale),
+            options,
+            scale,
+        );
+
+        let pens: Vec<GlyphPen> = glyphs
+            .iter()
+            .map(|glyph| GlyphPen {
+                font: glyph.font,
+                key: glyph.key,
+                pen: glyph.pen,
+                paint,
+            })
+            .collect();
+        self.render_glyphs(&pens);
+    }
+
+    /// Returns the glyphs `shaped_text` draws, in visual order.
+    #[cfg(feature = "shaping")]
+    pub fn shaped_glyphs(
+        &mut self,
+        text: &str,
+        font: FontId,
+        size: f32,
+        options: TextLayoutOptions,
+    ) -> Vec<ShapedGlyph> {
+        let scale = self.device_px_ratio;
+        let s = 1.0 / scale;
+        let font_index = self.font_index(font);
+
+        shaping::shape(
+            &self.glyph_cache,
+            text,
+            font_index,
+            GlyphCache::bucket_size(size * scale),
+            options,
+            scale,
+        )
+        .iter()
+        .map(|glyph| {
+            let (origin, size) = self
+                .glyph_cache
+                .glyph_rect(glyph.font, glyph.key, glyph.pen);
+            ShapedGlyph {
+                font: FontId { index: glyph.font },
+                glyph_id: glyph.key.glyph_index,
+                cluster: glyph.cluster,
+                rect: LocalRect::new(origin.into(), [size[0] as f32, size[1] as f32].into())
+                    .scale(s, s),
             }
-        }
+        })
+        .collect()
+    }
 
-        for prim in prims {
-            self.render(prim);
-        }
+    /// Calculates the bounds for text. Like `glyph_positions`, these are
+    /// the bounds of the glyphs' quads.
+    pub fn text_bounds(
+        &mut self,
+        text: &str,
+        font: FontId,
+        size: f32,
+        options: TextLayoutOptions,
+    ) -> LocalRect {
+        self.setup_layout(text, font, size, options);
+        self.layout_bounds()
     }
 
-    /// Calculates the bounds for text.
-    pub fn text_bounds(&mut self, text: &str, size: u32, max_width: Option<f32>) -> LocalRect {
-        self.setup_layout(text, size, max_width);
+    /// Calculates the bounds for attributed text.
+    pub fn attributed_text_bounds(
+        &mut self,
+        text: &AttributedText,
+        options: TextLayoutOptions,
+    ) -> LocalRect {
+        self.setup_attributed_layout(text, options);
+        self.layout_bounds()
+    }
 
+    fn layout_bounds(&self) -> LocalRect {
         let mut min = LocalPoint::new(f32::MAX, f32::MAX);
         let mut max = LocalPoint::new(f32::MIN, f32::MIN);
 
         let scale = self.device_px_ratio;
 
-        for glyph in self.layout.glyphs() {
+        for glyph in &self.glyphs {
             min = min.min([glyph.x / scale, glyph.y / scale].into());
             max = max.max(
                 [
@@ -765,21 +1438,24 @@ impl Vger {
         LocalRect::new(min, (max - min).into())
     }
 
-    /// Returns local coordinates of glyphs.
+    /// Returns the bounds of the glyphs' quads in local coordinates, as
+    /// drawn with no transform or a translation by whole device pixels.
+    /// Other translations move glyphs to different subpixel phases,
+    /// which can move their edges by a pixel.
     pub fn glyph_positions(
         &mut self,
         text: &str,
-        size: u32,
-        max_width: Option<f32>,
+        font: FontId,
+        size: f32,
+        options: TextLayoutOptions,
     ) -> Vec<LocalRect> {
-        let mut rects = vec![];
-        rects.reserve(text.len());
+        let mut rects = Vec::with_capacity(text.len());
 
-        self.setup_layout(text, size, max_width);
+        self.setup_layout(text, font, size, options);
 
         let s = 1.0 / self.device_px_ratio;
 
-        for glyph in self.layout.glyphs() {
+        for glyph in &self.glyphs {
             rects.push(
                 LocalRect::new(
                     [glyph.x, glyph.y].into(),
@@ -795,22 +1471,42 @@ impl Vger {
     pub fn line_metrics(
         &mut self,
         text: &str,
-        size: u32,
-        max_width: Option<f32>,
+        font: FontId,
+        size: f32,
+        options: TextLayoutOptions,
+    ) -> Vec<LineMetrics> {
+        self.setup_layout(text, font, size, options);
+        self.layout_line_metrics()
+    }
+
+    /// Returns the lines of attributed text. Glyph indices count the
+    /// characters of `AttributedText::text`.
+    pub fn attributed_line_metrics(
+        &mut self,
+        text: &AttributedText,
+        options: TextLayoutOptions,
     ) -> Vec<LineMetrics> {
-        self.setup_layout(text, size, max_width);
+        self.setup_attributed_layout(text, options);
+        self.layout_line_metrics()
+    }
+
+    fn layout_line_metrics(&self) -> Vec<LineMetrics> {
         let s = 1.0 / self.device_px_ratio;
 
         let mut rects = vec![];
-        rects.reserve(text.len());
 
-        let glyphs = self.layout.glyphs();
+        let glyphs = &self.glyphs;
 
         if let Some(lines) = self.layout.lines() {
             for line in lines {
                 let mut rect = LocalRect::zero();
 
-                for glyph in &glyphs[line.glyph_start..line.glyph_end] {
+                // glyph_end is inclusive. Lines without glyphs have
+                // empty bounds.
+                let line_glyphs = glyphs
+                    .get(line.glyph_start..=line.glyph_end)
+                    .unwrap_or_default();
+                for glyph in line_glyphs {
                     rect = rect.union(&LocalRect::new(
                         [glyph.x, glyph.y].into(),
                         [glyph.width as f32, glyph.height as f32].into(),
@@ -936,101 +1632,813 @@ impl Vger {
         outer_color: Color,
         glow: f32,
     ) -> PaintIndex {
-        self.add_paint(Paint::linear_gradient(
-            start.into(),
-            end.into(),
-            inner_color,
-            outer_color,
-            glow,
+        let mut paint =
+            Paint::linear_gradient(start.into(), end.into(), inner_color, outer_color, glow);
+        paint.dither = if self.dither_gradients { 1.0 } else { 0.0 };
+        self.add_paint(paint)
+    }
+
+    /// Box gradient paint. Fills the rounded rectangle with the inner
+    /// color, fading to the outer color over the feather distance.
+    /// Useful for drop shadows, inset panels and soft bevels.
+    pub fn box_gradient<R: Into<LocalRect>>(
+        &mut self,
+        rect: R,
+        radius: f32,
+        feather: f32,
+        inner_color: Color,
+        outer_color: Color,
+    ) -> PaintIndex {
+        let mut paint = Paint::box_gradient(rect.into(), radius, feather, inner_color, outer_color);
+        paint.dither = if self.dither_gradients { 1.0 } else { 0.0 };
+        self.add_paint(paint)
+    }
+
+    /// Checkerboard pattern paint, e.g. for transparency backgrounds.
+    pub fn checkerboard(
+        &mut self,
+        cell_size: f32,
+        angle: f32,
+        color0: Color,
+        color1: Color,
+    ) -> PaintIndex {
+        self.add_paint(Paint::pattern(
+            PaintType::Checkerboard,
+            cell_size,
+            0.0,
+            angle,
+            color0,
+            color1,
         ))
     }
 
+    /// Stripe pattern paint. Bands are width wide, rotated by
+    /// angle (radians).
+    pub fn stripes(&mut self, width: f32, angle: f32, color0: Color, color1: Color) -> PaintIndex {
+        self.add_paint(Paint::pattern(
+            PaintType::Stripes,
+            width,
+            0.0,
+            angle,
+            color0,
+            color1,
+        ))
+    }
+
+    /// Hatching pattern paint: lines of line_color every spacing
+    /// units over background, rotated by angle (radians).
+    pub fn hatch(
+        &mut self,
+        spacing: f32,
+        line_width: f32,
+        angle: f32,
+        line_color: Color,
+        background: Color,
+    ) -> PaintIndex {
+        self.add_paint(Paint::pattern(
+            PaintType::Hatch,
+            spacing,
+            line_width,
+            angle,
+            line_color,
+            background,
+        ))
+    }
+
+    /// Image paint. Maps the image onto the rectangle at origin with the
+    /// given size, rotated by angle (radians) about origin. The paint's
+    /// color is the image's color, after any `adjust_image_paint`
+    /// adjustments, with its alpha scaled by `alpha`.
+    pub fn image_pattern<Pt: Into<LocalPoint>, Sz: Into<LocalSize>>(
+        &mut self,
+        origin: Pt,
+        size: Sz,
+        angle: f32,
+        image: ImageIndex,
+        alpha: f32,
+    ) -> PaintIndex {
+        let paint =
+            Paint::image_pattern(origin.into(), size.into(), angle, image.index as i32, alpha);
+        self.add_paint(self.resolve_image(paint, image))
+    }
+
+    /// Copies an image paint with color adjustments applied, for
+    /// example to desaturate disabled icons or brighten hovered ones.
+    ///
+    /// Each color matrix is stored once per frame when the same
+    /// adjustments are applied to several paints in a row.
+    pub fn adjust_image_paint(
+        &mut self,
+        paint: PaintIndex,
+        adjustments: &ImageAdjustments,
+    ) -> Result<PaintIndex, ImageError> {
+        let mut paint = self
+            .frame_paint(paint)?
+            .tinted(adjustments.tint, adjustments.opacity);
+
+        if let Some(matrix) = &adjustments.color_matrix {
+            // Past the paint limit, add_paint drops the paint, so there's
+            // no need for its matrix. This keeps the matrices bounded too.
+            if self.paint_count < MAX_PRIMS {
+                let matrix = matrix.to_gpu();
+                let matrices = &mut self.scenes[self.cur_scene].color_matrices;
+                let len = matrices.len();
+                if len == 0 || matrices[len - 1] != matrix {
+                    matrices.push(matrix);
+                }
+                paint.color_matrix = matrices.len() as i32 - 1;
+            }
+        }
+
+        Ok(self.add_paint(paint))
+    }
+
+    /// Looks up a paint created this frame.
+    fn frame_paint(&self, paint: PaintIndex) -> Result<Paint, ImageError> {
+        let paints = &self.scenes[self.cur_scene].paints;
+        if paint.index < paints.len() {
+            Ok(paints[paint.index])
+        } else {
+            Err(ImageError::InvalidPaint)
+        }
+    }
+
+    /// Masks a paint with an image, placed as for `image_pattern`. The
+    /// paint's alpha is multiplied by the image's red channel, so an R8
+    /// icon can be drawn with any color, gradient or pattern.
+    ///
+    /// Paints that already use an image, including masked paints, can't
+    /// be masked.
+    pub fn mask_paint<Pt: Into<LocalPoint>, Sz: Into<LocalSize>>(
+        &mut self,
+        paint: PaintIndex,
+        mask: ImageIndex,
+        origin: Pt,
+        size: Sz,
+        angle: f32,
+    ) -> Result<PaintIndex, ImageError> {
+        if self.image(mask).is_none() {
+            return Err(ImageError::InvalidImage);
+        }
+
+        let paint = self.frame_paint(paint)?;
+        if paint.image != -1 {
+            return Err(ImageError::MaskedImagePaint);
+        }
+
+        let paint = paint.with_mask(origin.into(), size.into(), angle, mask.index as i32);
+        Ok(self.add_paint(self.resolve_image(paint, mask)))
+    }
+
+    /// Points an image paint at the image atlas if the image was
+    /// packed into it.
+    fn resolve_image(&self, mut paint: Paint, image: ImageIndex) -> Paint {
+        paint.image_generation = image.generation;
+        if let Some(img) = self.image(image) {
+            if let ImageStorage::Atlas { origin, .. } = img.storage {
+                paint.image = ATLAS_IMAGE;
+                paint.image_rect = [
+                    origin[0] as f32,
+                    origin[1] as f32,
+                    img.width as f32,
+                    img.height as f32,
+                ];
+            }
+        }
+        paint
+    }
+
+    /// Enables or disables packing small images into a shared atlas.
+    /// Rgba8Srgb images no larger than `ImageAtlas::MAX_IMAGE_SIZE`
+    /// with default sampler options that are created after this call
+    /// are packed, so drawing them doesn't need a separate draw call
+    /// each. Images that don't fit once the atlas is full get a texture
+    /// of their own.
+    ///
+    /// A deleted or resized image's space in the atlas is reused, from
+    /// the next `begin`, for images no larger than it, and the whole
+    /// atlas is repacked once all its images are deleted. Space wasted
+    /// by reusing a larger region isn't reclaimed until then.
+    pub fn set_image_atlas(&mut self, enable: bool) {
+        self.atlas_images = enable;
+    }
+
+    /// Enables or disables binding all images at once. It's on by
+    /// default when the device has `IMAGE_ARRAY_FEATURES` and limits
+    /// that fit the array; this setter only overrides that. Each layer
+    /// is then drawn with a single draw call, however many images it
+    /// uses.
+    ///
+    /// The array holds one texture fewer than the device's
+    /// `max_sampled_textures_per_shader_stage`, since the glyph atlas
+    /// takes one, which is 15 with wgpu's default limits. The image
+    /// atlas takes an element, as does every image slot, including
+    /// deleted ones not yet reused, and YUV images take one more per
+    /// chroma plane. The array's bind group is rebuilt at the next
+    /// `encode` after images are created, deleted or resized, so it
+    /// suits sets of images that rarely change.
+    ///
+    /// Images are bound one at a time instead, splitting draws at image
+    /// changes, when the features are missing, while textures from
+    /// `register_texture` are registered, or when there are more images
+    /// than the array holds.
+    pub fn set_image_array(&mut self, enable: bool) {
+        self.use_image_array = enable;
+    }
+
+    /// Whether `encode` can bind all images at once, see
+    /// `set_image_array`.
+    pub fn image_array_supported(&self) -> bool {
+        self.image_array.is_some()
+    }
+
+    /// Reserves space for an image in the image atlas, creating the
+    /// atlas on first use.
+    fn pack_image(&mut self, width: u32, height: u32) -> Option<ImageStorage> {
+        if self.image_atlas.is_none() {
+            let atlas = ImageAtlas::new(&self.device);
+            let bind_group =
+                self.create_image_bind_group(&atlas.create_view(), &SamplerOptions::default());
+            self.image_atlas = Some((atlas, bind_group));
+            self.images_changed();
+        }
+
+        let (atlas, _) = self.image_atlas.as_mut().unwrap();
+        let rect = atlas.add_region(width, height)?;
+        Some(ImageStorage::Atlas {
+            origin: [rect.x as u32, rect.y as u32],
+            reserved: [rect.width as u32, rect.height as u32],
+        })
+    }
+
+    /// Frees an image's region of the image atlas, if it has one.
+    fn release_storage(&mut self, storage: &ImageStorage) {
+        if let (ImageStorage::Atlas { origin, reserved }, Some((atlas, _))) =
+            (storage, &mut self.image_atlas)
+        {
+            atlas.remove_region(rect_packer::Rect {
+                x: origin[0] as i32,
+                y: origin[1] as i32,
+                width: reserved[0] as i32,
+                height: reserved[1] as i32,
+            });
+        }
+    }
+
+    /// Enables or disables dithering for gradient paints created
+    /// after this call. Dithering hides banding in subtle gradients
+    /// on 8-bit render targets.
+    pub fn set_gradient_dither(&mut self, dither: bool) {
+        self.dither_gradients = dither;
+    }
+
     /// Create an image from pixel data in memory.
-    /// Must be RGBA8.
-    pub fn create_image_pixels(&mut self, data: &[u8], width: u32, height: u32) -> ImageIndex {
-        let texture_size = wgpu::Extent3d {
+    ///
+    /// `bytes_per_row` is the source stride, or `None` for tightly
+    /// packed rows. `options` sets filtering and wrapping, and whether
+    /// a mip chain is generated.
+    pub fn create_image_pixels(
+        &mut self,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        format: ImageFormat,
+        bytes_per_row: Option<u32>,
+        options: SamplerOptions,
+    ) -> Result<ImageIndex, ImageError> {
+        let max_dimension = self.device.limits().max_texture_dimension_2d;
+        let stride =
+            image::validate_pixels(data, width, height, format, bytes_per_row, max_dimension)?;
+
+        let atlas_storage = if self.atlas_images
+            && format == ImageFormat::Rgba8Srgb
+            && options == SamplerOptions::default()
+        {
+            self.pack_image(width, height)
+        } else {
+            None
+        };
+
+        // Images that don't fit in the atlas get a texture of their own.
+        let storage = match atlas_storage {
+            Some(storage) => storage,
+            None => self.create_image_storage(width, height, format, &options),
+        };
+
+        let image = Image {
             width,
             height,
-            depth_or_array_layers: 1,
+            format,
+            options,
+            storage,
         };
 
-        let texture_desc = wgpu::TextureDescriptor {
-            size: texture_size,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
-            label: Some("lyte image"),
-            view_formats: &[wgpu::TextureFormat::Rgba8UnormSrgb],
+        image.write(
+            &self.queue,
+            self.image_atlas
+                .as_ref()
+                .map(|(atlas, _)| &atlas.atlas_texture),
+            [0, 0],
+            [width, height],
+            data,
+            stride,
+        );
+
+        let index = self.insert_image(image);
+        self.generate_mipmaps(index.index, euclid::rect(0, 0, width, height));
+
+        Ok(index)
+    }
+
+    /// Create an image from a PNG or JPEG file in memory.
+    ///
+    /// Palette, grayscale and 16 bit images are converted to 8 bit RGBA.
+    /// Colors keep straight alpha, as PNGs store them, because vger
+    /// blends with straight alpha; fully transparent pixels take their
+    /// neighbors' colors so filtering doesn't darken edges. EXIF
+    /// orientation isn't applied, so the first row of the file is the
+    /// top of the image. Images larger than the device's texture size
+    /// limit are rejected before they're decoded.
+    #[cfg(feature = "image-decode")]
+    pub fn create_image_from_bytes(&mut self, bytes: &[u8]) -> Result<ImageIndex, ImageError> {
+        let max_dimension = self.device.limits().max_texture_dimension_2d;
+        let image = decode::decode(bytes, max_dimension)?;
+        self.create_image_pixels(
+            &image.data,
+            image.width,
+            image.height,
+            ImageFormat::Rgba8Srgb,
+            None,
+            SamplerOptions::default(),
+        )
+    }
+
+    fn create_image_storage(
+        &self,
+        width: u32,
+        height: u32,
+        format: ImageFormat,
+        options: &SamplerOptions,
+    ) -> ImageStorage {
+        let texture = Image::create_texture(&self.device, width, height, format, options);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = self.create_image_bind_group(&view, options);
+        ImageStorage::Texture {
+            texture,
+            bind_group,
+        }
+    }
+
+    fn create_image_bind_group(
+        &self,
+        texture_view: &wgpu::TextureView,
+        options: &SamplerOptions,
+    ) -> wgpu::BindGroup {
+        self.create_planes_bind_group(
+            [texture_view, &self.empty_view, &self.empty_view],
+            &self.image_info,
+            options,
+        )
+    }
+
+    /// Bind group for an image made of up to three planes. Unused
+    /// planes are bound to `empty_view`.
+    fn create_planes_bind_group(
+        &self,
+        planes: [&wgpu::TextureView; 3],
+        info: &GPUVec<ImageInfo>,
+        options: &SamplerOptions,
+    ) -> wgpu::BindGroup {
+        let sampler = options.create_sampler(&self.device);
+
+        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.image_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(planes[0]),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(planes[1]),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(planes[2]),
+                },
+                info.bind_group_entry(4),
+            ],
+            label: Some("vger bind group"),
+        })
+    }
+
+    /// Create a YUV image, such as a decoded video frame, from one
+    /// slice of pixel data per plane. NV12 images take a Y plane and an
+    /// interleaved UV plane, I420 images separate Y, U and V planes.
+    /// Chroma planes are half the size of the Y plane, rounded up.
+    ///
+    /// Conversion to RGB happens in the fragment shader, so frames can
+    /// be uploaded as they come out of the decoder. `options.mipmaps`
+    /// is ignored.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_image_yuv(
+        &mut self,
+        format: YuvFormat,
+        width: u32,
+        height: u32,
+        color_space: YuvColorSpace,
+        range: YuvRange,
+        planes: &[YuvPlane],
+        options: SamplerOptions,
+    ) -> Result<ImageIndex, ImageError> {
+        let max_dimension = self.device.limits().max_texture_dimension_2d;
+        let strides = Self::validate_planes(format, [width, height], planes, max_dimension)?;
+
+        let info = ImageInfo::yuv(format, color_space, range);
+        let options = SamplerOptions {
+            mipmaps: false,
+            ..options
         };
+        let storage = self.create_yuv_storage(format, width, height, info, &options);
+
+        if let ImageStorage::Yuv {
+            planes: textures, ..
+        } = &storage
+        {
+            for (plane, (texture, stride)) in textures.iter().zip(strides).enumerate() {
+                image::write_texture(
+                    &self.queue,
+                    texture,
+                    [0, 0],
+                    format.plane_size(plane, width, height),
+                    planes[plane].data,
+                    stride,
+                );
+            }
+        }
 
-        let texture = self.device.create_texture(&texture_desc);
+        Ok(self.insert_image(Image {
+            width,
+            height,
+            // Format of the Y plane.
+            format: ImageFormat::R8,
+            options,
+            storage,
+        }))
+    }
 
-        let buffer = self
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Temp Buffer"),
-                contents: data,
-                usage: wgpu::BufferUsages::COPY_SRC,
+    /// Checks the planes of a YUV image or update of the given size,
+    /// returning the stride of each plane.
+    fn validate_planes(
+        format: YuvFormat,
+        size: [u32; 2],
+        planes: &[YuvPlane],
+        max_dimension: u32,
+    ) -> Result<Vec<u32>, ImageError> {
+        let formats = format.plane_formats();
+        if planes.len() != formats.len() {
+            return Err(ImageError::InvalidPlanes {
+                expected: formats.len(),
+                found: planes.len(),
             });
+        }
+
+        planes
+            .iter()
+            .zip(formats)
+            .enumerate()
+            .map(|(i, (plane, plane_format))| {
+                let [width, height] = format.plane_size(i, size[0], size[1]);
+                image::validate_pixels(
+                    plane.data,
+                    width,
+                    height,
+                    *plane_format,
+                    plane.bytes_per_row,
+                    max_dimension,
+                )
+            })
+            .collect()
+    }
+
+    fn create_yuv_storage(
+        &self,
+        format: YuvFormat,
+        width: u32,
+        height: u32,
+        info: ImageInfo,
+        options: &SamplerOptions,
+    ) -> ImageStorage {
+        let textures: Vec<wgpu::Texture> = format
+            .plane_formats()
+            .iter()
+            .enumerate()
+            .map(|(i, plane_format)| {
+                let [w, h] = format.plane_size(i, width, height);
+                Image::create_texture(&self.device, w, h, *plane_format, options)
+            })
+            .collect();
+
+        let views: Vec<wgpu::TextureView> = textures
+            .iter()
+            .map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()))
+            .collect();
+
+        let mut info_buffer = GPUVec::new_uniforms(&self.device, "yuv image info");
+        info_buffer.push(info);
+        info_buffer.update(&self.device, &self.queue);
+
+        let bind_group = self.create_planes_bind_group(
+            [
+                &views[0],
+                &views[1],
+                views.get(2).unwrap_or(&self.empty_view),
+            ],
+            &info_buffer,
+            options,
+        );
+
+        ImageStorage::Yuv {
+            format,
+            info,
+            planes: textures,
+            bind_group,
+        }
+    }
+
+    /// Replaces a rectangle of a YUV image, given one slice of pixel
+    /// data per plane as for `create_image_yuv`. Chroma is shared by
+    /// 2x2 blocks of pixels, so the rectangle must start on even
+    /// coordinates and have an even size unless it reaches the right
+    /// or bottom edge.
+    pub fn update_image_yuv(
+        &mut self,
+        image: ImageIndex,
+        rect: ImageRect,
+        planes: &[YuvPlane],
+    ) -> Result<(), ImageError> {
+        let img = self.image(image).ok_or(ImageError::InvalidImage)?;
+
+        let (format, textures) = match &img.storage {
+            ImageStorage::Yuv { format, planes, .. } => (*format, planes),
+            ImageStorage::External { .. } => return Err(ImageError::ExternalTexture),
+            _ => return Err(ImageError::NotYuv),
+        };
+
+        image::check_bounds(rect, img.width, img.height)?;
+
+        let aligned = |origin: u32, size: u32, max: u32| {
+            origin.is_multiple_of(2) && (size.is_multiple_of(2) || origin + size == max)
+        };
+        if !aligned(rect.origin.x, rect.size.width, img.width)
+            || !aligned(rect.origin.y, rect.size.height, img.height)
+        {
+            return Err(ImageError::UnalignedRect);
+        }
+
+        let size = rect.size.to_array();
+        let strides = Self::validate_planes(format, size, planes, img.width.max(img.height))?;
+
+        for (plane, (texture, stride)) in textures.iter().zip(strides).enumerate() {
+            let origin = if plane == 0 {
+                rect.origin.to_array()
+            } else {
+                [rect.origin.x / 2, rect.origin.y / 2]
+            };
+            image::write_texture(
+                &self.queue,
+                texture,
+                origin,
+                format.plane_size(plane, size[0], size[1]),
+                planes[plane].data,
+                stride,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Rebuilds the part of the mip chain that depends on `region` of
+    /// level 0, if the texture has a chain.
+    fn generate_mipmaps(&mut self, index: usize, region: ImageRect) {
+        let texture = match &self.images[index].image {
+            Some(Image {
+                storage: ImageStorage::Texture { texture, .. },
+                ..
+            }) if texture.mip_level_count() > 1 => texture,
+            _ => return,
+        };
 
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("texture_buffer_copy_encoder"),
+                label: Some("mipmap encoder"),
             });
+        self.mipmaps
+            .generate(&self.device, &mut encoder, texture, region);
+        self.queue.submit(Some(encoder.finish()));
+    }
 
-        let image_size = wgpu::Extent3d {
-            width,
-            height,
-            depth_or_array_layers: 1,
-        };
+    /// Replaces a rectangle of an image's pixels. The data is in the
+    /// image's format, with `bytes_per_row` as for `create_image_pixels`.
+    ///
+    /// Takes effect at the next `encode`, so an image can be updated
+    /// every frame without reallocating. For images with mipmaps, only
+    /// the part of each mip level under `rect` is regenerated.
+    pub fn update_image(
+        &mut self,
+        image: ImageIndex,
+        rect: ImageRect,
+        data: &[u8],
+        bytes_per_row: Option<u32>,
+    ) -> Result<(), ImageError> {
+        let img = self.image(image).ok_or(ImageError::InvalidImage)?;
+
+        match &img.storage {
+            ImageStorage::External { .. } => return Err(ImageError::ExternalTexture),
+            ImageStorage::Yuv { planes, .. } => {
+                return Err(ImageError::InvalidPlanes {
+                    expected: planes.len(),
+                    found: 1,
+                })
+            }
+            _ => (),
+        }
 
-        encoder.copy_buffer_to_texture(
-            wgpu::ImageCopyBuffer {
-                buffer: &buffer,
-                layout: wgpu::ImageDataLayout {
-                    offset: 0,
-                    bytes_per_row: Some(width * 4),
-                    rows_per_image: Some(height),
-                },
-            },
-            wgpu::ImageCopyTexture {
-                texture: &texture,
-                mip_level: 0,
-                aspect: wgpu::TextureAspect::All,
-                origin: wgpu::Origin3d::ZERO,
-            },
-            image_size,
+        let (width, height) = (img.width, img.height);
+        image::check_bounds(rect, width, height)?;
+
+        let stride = image::validate_pixels(
+            data,
+            rect.size.width,
+            rect.size.height,
+            img.format,
+            bytes_per_row,
+            width.max(height),
+        )?;
+
+        img.write(
+            &self.queue,
+            self.image_atlas
+                .as_ref()
+                .map(|(atlas, _)| &atlas.atlas_texture),
+            rect.origin.to_array(),
+            rect.size.to_array(),
+            data,
+            stride,
         );
 
-        self.queue.submit(std::iter::once(encoder.finish()));
+        self.generate_mipmaps(image.index, rect);
 
-        let index = ImageIndex {
-            index: self.images.len(),
-        };
+        Ok(())
+    }
 
-        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    /// Reallocates an image at a new size, keeping its format and index.
+    /// The new contents are cleared to transparent black, or for YUV
+    /// images to all zero planes.
+    pub fn resize_image(
+        &mut self,
+        image: ImageIndex,
+        width: u32,
+        height: u32,
+    ) -> Result<(), ImageError> {
+        let img = self.image(image).ok_or(ImageError::InvalidImage)?;
+
+        if let ImageStorage::External { .. } = img.storage {
+            return Err(ImageError::ExternalTexture);
+        }
 
-        self.images.push(Some(texture));
+        let (format, options) = (img.format, img.options);
 
-        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &self.image_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 1,
-                resource: wgpu::BindingResource::TextureView(&texture_view),
-            }],
-            label: Some("vger bind group"),
+        let max = self.device.limits().max_texture_dimension_2d;
+        if width == 0 || height == 0 || width > max || height > max {
+            return Err(ImageError::InvalidSize { width, height, max });
+        }
+
+        // Resized images get a texture of their own, even if they were
+        // in the image atlas.
+        let storage = match img.storage {
+            ImageStorage::Yuv {
+                format: yuv_format,
+                info,
+                ..
+            } => self.create_yuv_storage(yuv_format, width, height, info, &options),
+            _ => self.create_image_storage(width, height, format, &options),
+        };
+
+        let old = self.images[image.index].image.replace(Image {
+            width,
+            height,
+            format,
+            options,
+            storage,
         });
+        if let Some(old) = old {
+            self.release_storage(&old.storage);
+        }
+        self.images_changed();
+
+        Ok(())
+    }
+
+    /// Looks up a live image, returning `None` for deleted images.
+    fn image(&self, image: ImageIndex) -> Option<&Image> {
+        self.images
+            .get(image.index)
+            .filter(|slot| slot.generation == image.generation)
+            .and_then(|slot| slot.image.as_ref())
+    }
+
+    /// Stores an image, reusing the slot of a deleted image if possible.
+    fn insert_image(&mut self, image: Image) -> ImageIndex {
+        let index = match self.free_images.pop() {
+            Some(index) => index,
+            None => {
+                self.images.push(ImageSlot::default());
+                self.images.len() - 1
+            }
+        };
+
+        self.images_changed();
 
-        self.image_bind_groups.push(Some(bind_group));
+        let slot = &mut self.images[index];
+        slot.image = Some(image);
+
+        ImageIndex {
+            index,
+            generation: slot.generation,
+        }
+    }
 
-        index
+    /// Rebuilds the image array's bind group at the next `encode`.
+    fn images_changed(&mut self) {
+        if let Some((array, _)) = &mut self.image_array {
+            array.invalidate();
+        }
     }
 
+    /// Deletes an image, freeing its GPU resources. Deleting an image
+    /// twice is harmless. Paints already created with the image draw
+    /// nothing, except paints of images in the image atlas, which draw
+    /// the image until the next `begin`, when its space in the atlas is
+    /// freed.
     pub fn delete_image(&mut self, image: ImageIndex) {
-        self.images[image.index] = None;
-        self.image_bind_groups[image.index] = None;
+        if self.image(image).is_some() {
+            let slot = &mut self.images[image.index];
+            let old = slot.image.take();
+            slot.generation = slot.generation.wrapping_add(1);
+            if let Some(old) = old {
+                self.release_storage(&old.storage);
+            }
+            self.free_images.push(image.index);
+            self.images_changed();
+        }
+    }
+
+    /// Makes a texture owned by the caller usable with image paints,
+    /// for example to composite 3D or video content into the UI.
+    ///
+    /// The view must be a 2D view with a filterable float sample type.
+    /// `wgpu::TextureView` doesn't expose its texture's size, which
+    /// `draw_image` needs to fit and crop the image, so it is given as
+    /// width and height. `options` picks the sampler, as for
+    /// `create_image_pixels`, except that `options.mipmaps` is ignored;
+    /// the view's own mip levels are sampled.
+    ///
+    /// The texture is kept alive until `unregister_texture`, and its
+    /// pixels can't be changed through `update_image` or
+    /// `resize_image`.
+    ///
+    /// Views can't be cloned, so only a bind group for the view is
+    /// kept, and it can't join the binding array of
+    /// `set_image_array`. While any texture is registered, every image,
+    /// not just registered ones, is bound one at a time, splitting
+    /// draws at image changes.
+    pub fn register_texture(
+        &mut self,
+        view: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+        options: SamplerOptions,
+    ) -> ImageIndex {
+        let bind_group = self.create_image_bind_group(view, &options);
+
+        self.insert_image(Image {
+            width,
+            height,
+            // Not used for external textures.
+            format: ImageFormat::Rgba8,
+            options,
+            storage: ImageStorage::External { bind_group },
+        })
+    }
+
+    /// Releases a texture registered with `register_texture`.
+    pub fn unregister_texture(&mut self, image: ImageIndex) {
+        self.delete_image(image);
     }
 }
@@ -0,0 +1,222 @@
+use crate::defs::ImageRect;
+use std::collections::HashMap;
+
+/// Generates mip chains for images by blitting each level into the next.
+pub struct MipmapGenerator {
+    shader: wgpu::ShaderModule,
+    sampler: wgpu::Sampler,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipelines: HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>,
+}
+
+impl MipmapGenerator {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("mipmap shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!(
+                "mipmap.wgsl"
+            ))),
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("mipmap"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("mipmap_bind_group_layout"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("mipmap pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        Self {
+            shader,
+            sampler,
+            bind_group_layout,
+            pipeline_layout,
+            pipelines: HashMap::new(),
+        }
+    }
+
+    /// Number of levels in a full mip chain.
+    pub fn mip_level_count(width: u32, height: u32) -> u32 {
+        32 - width.max(height).max(1).leading_zeros()
+    }
+
+    /// The part of a mip level that depends on `region` of the level
+    /// above it, which is `src` pixels across. Bilinear samples reach a
+    /// pixel past the region, so this errs a pixel wide on each side.
+    fn next_region(region: ImageRect, src: [u32; 2], dst: [u32; 2]) -> ImageRect {
+        let range = |start: u32, end: u32, src: u32, dst: u32| {
+            let start = (start as u64 * dst as u64 / src as u64).saturating_sub(1);
+            let end = ((end as u64 * dst as u64).div_ceil(src as u64) + 1).min(dst as u64);
+            (start as u32, end as u32)
+        };
+        let (x0, x1) = range(region.min_x(), region.max_x(), src[0], dst[0]);
+        let (y0, y1) = range(region.min_y(), region.max_y(), src[1], dst[1]);
+        euclid::rect(x0, y0, x1 - x0, y1 - y0)
+    }
+
+    /// Fills mip levels 1 and up of texture from level 0, redrawing only
+    /// the parts of each level that depend on `region` of level 0. The
+    /// texture must have been created with `RENDER_ATTACHMENT` usage.
+    pub fn generate(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        region: ImageRect,
+    ) {
+        let format = texture.format();
+        let shader = &self.shader;
+        let pipeline_layout = &self.pipeline_layout;
+
+        let pipeline = self.pipelines.entry(format).or_insert_with(|| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("mipmap pipeline"),
+                layout: Some(pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: "vs_main",
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: "fs_main",
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    targets: &[Some(format.into())],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        });
+
+        let views: Vec<wgpu::TextureView> = (0..texture.mip_level_count())
+            .map(|level| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("mip level"),
+                    base_mip_level: level,
+                    mip_level_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        let mut region = region;
+        for level in 1..views.len() {
+            let size = texture
+                .size()
+                .mip_level_size(level as u32, wgpu::TextureDimension::D2);
+            let prev = texture
+                .size()
+                .mip_level_size(level as u32 - 1, wgpu::TextureDimension::D2);
+            region =
+                Self::next_region(region, [prev.width, prev.height], [size.width, size.height]);
+
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&views[level - 1]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+                label: Some("mipmap bind group"),
+            });
+
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("mipmap pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &views[level],
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            rpass.set_scissor_rect(
+                region.origin.x,
+                region.origin.y,
+                region.size.width,
+                region.size.height,
+            );
+            rpass.set_pipeline(pipeline);
+            rpass.set_bind_group(0, &bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_mip_level_count() {
+        assert_eq!(MipmapGenerator::mip_level_count(1, 1), 1);
+        assert_eq!(MipmapGenerator::mip_level_count(2, 1), 2);
+        assert_eq!(MipmapGenerator::mip_level_count(256, 256), 9);
+        assert_eq!(MipmapGenerator::mip_level_count(300, 20), 9);
+        assert_eq!(MipmapGenerator::mip_level_count(512, 1024), 11);
+    }
+
+    #[test]
+    fn test_next_region() {
+        let next = |region: ImageRect, src: [u32; 2], dst: [u32; 2]| {
+            let r = MipmapGenerator::next_region(region, src, dst);
+            [r.origin.x, r.origin.y, r.size.width, r.size.height]
+        };
+
+        // A pixel of margin either side of the halved region.
+        assert_eq!(
+            next(euclid::rect(8, 8, 8, 8), [64, 64], [32, 32]),
+            [3, 3, 6, 6]
+        );
+
+        // Clamped to the level.
+        assert_eq!(
+            next(euclid::rect(0, 0, 64, 64), [64, 64], [32, 32]),
+            [0, 0, 32, 32]
+        );
+        assert_eq!(next(euclid::rect(2, 0, 1, 3), [3, 3], [1, 1]), [0, 0, 1, 1]);
+    }
+}
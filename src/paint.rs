@@ -1,6 +1,31 @@
 use crate::color::*;
 use crate::defs::*;
 
+#[derive(Copy, Clone)]
+#[allow(dead_code)]
+pub enum PaintType {
+    /// Solid colors, linear gradients and images.
+    Linear,
+
+    /// Feathered rounded rectangle (nanovg's box gradient).
+    Box,
+
+    /// Alternating square cells.
+    Checkerboard,
+
+    /// Alternating bands of equal width.
+    Stripes,
+
+    /// Lines of one color over another.
+    Hatch,
+
+    /// Nine-slice image with stretched edges and center.
+    NineSlice,
+
+    /// Nine-slice image with tiled edges and center.
+    NineSliceTile,
+}
+
 #[derive(Clone, Copy)]
 #[repr(C)]
 pub struct Paint {
@@ -11,15 +36,92 @@ pub struct Paint {
 
     inner_color: Color, // vec4<f32>
     outer_color: Color, // vec4<f32>
+
+    /// Non-zero to apply ordered dithering to the paint's output.
+    pub dither: f32,
+
+    /// Type of paint.
+    kind: u32,
+
+    /// Generation of the image handle, so paints referring to a
+    /// deleted image can be detected.
+    pub image_generation: u32,
+
+    pad: f32,
+
+    /// Paint type specific parameters. For box gradients these are
+    /// the half extent, corner radius and feather. For patterns, the
+    /// cell size and (for hatching) the line width. For nine-slice
+    /// images, the destination size, image size and insets.
+    params: [f32; 8], // array<vec4<f32>, 2>
+
+    /// For images packed into the image atlas, the image's origin and
+    /// size within the atlas in pixels. Zero size otherwise.
+    pub image_rect: [f32; 4], // vec4<f32>
+
+    /// For mask paints, local space to mask texture coordinates.
+    mask_xform: LocalTransform, // mat3x2<f32>
+
+    /// Non-zero if the image is a mask whose red channel multiplies
+    /// the paint's alpha.
+    mask: u32,
+
+    /// Index of the color matrix applied to image colors, or -1.
+    pub color_matrix: i32,
+}
+
+/// Local space to texture coordinates for an image placed at origin
+/// with the given size, rotated by angle about origin.
+fn image_xform(origin: LocalPoint, size: LocalSize, angle: f32) -> LocalTransform {
+    // Texture space to local space. Flips v so the first row of the
+    // image is at the top.
+    let xform = LocalTransform::scale(1.0, -1.0)
+        .then_translate(LocalVector::new(0.0, 1.0))
+        .then_scale(size.width, size.height)
+        .then_rotate(euclid::Angle::radians(angle))
+        .then_translate(origin.to_vector());
+
+    xform.inverse().unwrap_or_else(LocalTransform::identity)
+}
+
+fn sd_box(p: LocalPoint, b: LocalVector, r: f32) -> f32 {
+    let d = p.to_vector().abs() - b + LocalVector::new(r, r);
+    d.max(LocalVector::zero()).length() + d.x.max(d.y).min(0.0) - r
 }
 
 impl Paint {
     #[allow(dead_code)]
     pub fn apply(&self, p: LocalPoint) -> Color {
         let local_point = self.xform.transform_point(p);
-        let d = local_point
-            .clamp(LocalPoint::zero(), LocalPoint::new(1.0, 1.0))
-            .x;
+
+        // Point sampled pulse train, see filteredPulse in the shader.
+        let pulse = |x: f32, w: f32| if x - x.floor() < w { 1.0 } else { 0.0 };
+
+        let d = match self.kind {
+            k if k == PaintType::Box as u32 => {
+                let [ex, ey, radius, feather, ..] = self.params;
+                let dist = sd_box(local_point, LocalVector::new(ex, ey), radius);
+                ((dist + feather * 0.5) / feather).clamp(0.0, 1.0)
+            }
+            k if k == PaintType::Checkerboard as u32 => {
+                let q = local_point / (2.0 * self.params[0]);
+                let sx = pulse(q.x, 0.5);
+                let sy = pulse(q.y, 0.5);
+                sx + sy - 2.0 * sx * sy
+            }
+            k if k == PaintType::Stripes as u32 => {
+                1.0 - pulse(local_point.x / (2.0 * self.params[0]), 0.5)
+            }
+            k if k == PaintType::Hatch as u32 => {
+                let w = self.params[1] / self.params[0];
+                1.0 - pulse(local_point.x / self.params[0] + 0.5 * w, w)
+            }
+            _ => {
+                local_point
+                    .clamp(LocalPoint::zero(), LocalPoint::new(1.0, 1.0))
+                    .x
+            }
+        };
 
         self.inner_color.mix(self.outer_color, d)
     }
@@ -31,6 +133,15 @@ impl Paint {
             outer_color: color,
             image: -1,
             glow: 0.0,
+            dither: 0.0,
+            kind: PaintType::Linear as u32,
+            image_generation: 0,
+            pad: 0.0,
+            image_rect: [0.0; 4],
+            mask_xform: LocalTransform::identity(),
+            mask: 0,
+            color_matrix: -1,
+            params: [0.0; 8],
         }
     }
 
@@ -57,6 +168,199 @@ impl Paint {
             outer_color,
             image: -1,
             glow,
+            dither: 0.0,
+            kind: PaintType::Linear as u32,
+            image_generation: 0,
+            pad: 0.0,
+            image_rect: [0.0; 4],
+            mask_xform: LocalTransform::identity(),
+            mask: 0,
+            color_matrix: -1,
+            params: [0.0; 8],
+        }
+    }
+
+    /// Box gradient: inner color inside the rounded rectangle, fading
+    /// to the outer color across the feather distance centered on the
+    /// rectangle's edge.
+    pub fn box_gradient(
+        rect: LocalRect,
+        radius: f32,
+        feather: f32,
+        inner_color: Color,
+        outer_color: Color,
+    ) -> Self {
+        let center = rect.center();
+        let half_size = rect.size * 0.5;
+
+        Self {
+            xform: LocalTransform::translation(-center.x, -center.y),
+            inner_color,
+            outer_color,
+            image: -1,
+            glow: 0.0,
+            dither: 0.0,
+            kind: PaintType::Box as u32,
+            image_generation: 0,
+            pad: 0.0,
+            image_rect: [0.0; 4],
+            mask_xform: LocalTransform::identity(),
+            mask: 0,
+            color_matrix: -1,
+            params: [
+                half_size.width,
+                half_size.height,
+                radius.min(half_size.width).min(half_size.height),
+                feather.max(0.0001),
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+            ],
+        }
+    }
+
+    /// Image pattern. The image's top left corner is at origin + (0, size.height),
+    /// since local space is y-up.
+    pub fn image_pattern(
+        origin: LocalPoint,
+        size: LocalSize,
+        angle: f32,
+        image: i32,
+        alpha: f32,
+    ) -> Self {
+        Self {
+            xform: image_xform(origin, size, angle),
+            inner_color: Color::WHITE.alpha(alpha),
+            outer_color: Color::WHITE.alpha(alpha),
+            image,
+            glow: 0.0,
+            dither: 0.0,
+            kind: PaintType::Linear as u32,
+            image_generation: 0,
+            pad: 0.0,
+            image_rect: [0.0; 4],
+            mask_xform: LocalTransform::identity(),
+            mask: 0,
+            color_matrix: -1,
+            params: [0.0; 8],
+        }
+    }
+
+    /// Nine-slice image. Maps the image onto dst so that the corners
+    /// given by insets (left, top, right, bottom, in pixels) keep one
+    /// local unit per pixel and the edges and center stretch or tile.
+    pub fn nine_slice(
+        dst: LocalRect,
+        image_size: LocalSize,
+        insets: [f32; 4],
+        tile: bool,
+        image: i32,
+        alpha: f32,
+    ) -> Self {
+        // Local space to distance from the top left of dst, y-down like
+        // image rows.
+        let xform = LocalTransform::translation(-dst.min_x(), -dst.max_y()).then_scale(1.0, -1.0);
+
+        let [left, top, right, bottom] = insets;
+
+        Self {
+            xform,
+            inner_color: Color::WHITE.alpha(alpha),
+            outer_color: Color::WHITE.alpha(alpha),
+            image,
+            glow: 0.0,
+            dither: 0.0,
+            kind: if tile {
+                PaintType::NineSliceTile
+            } else {
+                PaintType::NineSlice
+            } as u32,
+            image_generation: 0,
+            pad: 0.0,
+            image_rect: [0.0; 4],
+            mask_xform: LocalTransform::identity(),
+            mask: 0,
+            color_matrix: -1,
+            params: [
+                dst.size.width,
+                dst.size.height,
+                image_size.width,
+                image_size.height,
+                left,
+                top,
+                right,
+                bottom,
+            ],
+        }
+    }
+
+    /// Masks the paint with an image placed as for `image_pattern`.
+    /// The paint's alpha is multiplied by the image's red channel. The
+    /// mask takes the place of the paint's image, so the paint must not
+    /// have one.
+    pub fn with_mask(
+        mut self,
+        origin: LocalPoint,
+        size: LocalSize,
+        angle: f32,
+        image: i32,
+    ) -> Self {
+        self.mask_xform = image_xform(origin, size, angle);
+        self.mask = 1;
+        self.image = image;
+        self
+    }
+
+    /// Multiplies the paint's colors by tint, and its alpha by opacity.
+    pub fn tinted(mut self, tint: Color, opacity: f32) -> Self {
+        let mul = |c: Color| {
+            Color::new(
+                c.r * tint.r,
+                c.g * tint.g,
+                c.b * tint.b,
+                c.a * tint.a * opacity,
+            )
+        };
+        self.inner_color = mul(self.inner_color);
+        self.outer_color = mul(self.outer_color);
+        self
+    }
+
+    /// Procedural pattern rotated by angle (radians) about the origin.
+    /// Cells of color0 alternate with color1.
+    pub fn pattern(
+        kind: PaintType,
+        cell_size: f32,
+        line_width: f32,
+        angle: f32,
+        color0: Color,
+        color1: Color,
+    ) -> Self {
+        Self {
+            xform: LocalTransform::rotation(euclid::Angle::radians(-angle)),
+            inner_color: color0,
+            outer_color: color1,
+            image: -1,
+            glow: 0.0,
+            dither: 0.0,
+            kind: kind as u32,
+            image_generation: 0,
+            pad: 0.0,
+            image_rect: [0.0; 4],
+            mask_xform: LocalTransform::identity(),
+            mask: 0,
+            color_matrix: -1,
+            params: [
+                cell_size.max(0.0001),
+                line_width,
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+            ],
         }
     }
 }
@@ -68,7 +372,7 @@ mod tests {
 
     #[test]
     fn test_paint_size() {
-        assert_eq!(std::mem::size_of::<Paint>(), 64);
+        assert_eq!(std::mem::size_of::<Paint>(), 160);
     }
 
     #[test]
@@ -116,4 +420,107 @@ mod tests {
             assert_eq!(paint.apply(LocalPoint::new(3.0, 0.0)), Color::gray(1.0));
         }
     }
+
+    #[test]
+    fn test_box_gradient() {
+        let paint = Paint::box_gradient(
+            LocalRect::new(LocalPoint::new(10.0, 10.0), LocalSize::new(20.0, 10.0)),
+            0.0,
+            4.0,
+            Color::gray(1.0),
+            Color::gray(0.0),
+        );
+
+        // Deep inside the box.
+        assert_eq!(paint.apply(LocalPoint::new(20.0, 15.0)), Color::gray(1.0));
+
+        // On the edge, halfway through the feather.
+        assert_eq!(paint.apply(LocalPoint::new(30.0, 15.0)), Color::gray(0.5));
+        assert_eq!(paint.apply(LocalPoint::new(20.0, 10.0)), Color::gray(0.5));
+
+        // Past the feather.
+        assert_eq!(paint.apply(LocalPoint::new(40.0, 15.0)), Color::gray(0.0));
+    }
+
+    #[test]
+    fn test_patterns() {
+        let black = Color::gray(0.0);
+        let white = Color::gray(1.0);
+
+        let checker = Paint::pattern(PaintType::Checkerboard, 10.0, 0.0, 0.0, black, white);
+        assert_eq!(checker.apply(LocalPoint::new(5.0, 5.0)), black);
+        assert_eq!(checker.apply(LocalPoint::new(15.0, 5.0)), white);
+        assert_eq!(checker.apply(LocalPoint::new(5.0, 15.0)), white);
+        assert_eq!(checker.apply(LocalPoint::new(15.0, 15.0)), black);
+
+        let stripes = Paint::pattern(PaintType::Stripes, 10.0, 0.0, 0.0, black, white);
+        assert_eq!(stripes.apply(LocalPoint::new(5.0, 100.0)), black);
+        assert_eq!(stripes.apply(LocalPoint::new(15.0, 100.0)), white);
+
+        // Rotated a quarter turn, stripes run along x.
+        let rotated = Paint::pattern(
+            PaintType::Stripes,
+            10.0,
+            0.0,
+            std::f32::consts::FRAC_PI_2,
+            black,
+            white,
+        );
+        assert_eq!(rotated.apply(LocalPoint::new(100.0, 5.0)), black);
+        assert_eq!(rotated.apply(LocalPoint::new(100.0, -5.0)), white);
+
+        let hatch = Paint::pattern(PaintType::Hatch, 10.0, 2.0, 0.0, black, white);
+        assert_eq!(hatch.apply(LocalPoint::new(0.5, 3.0)), black);
+        assert_eq!(hatch.apply(LocalPoint::new(5.0, 3.0)), white);
+        assert_eq!(hatch.apply(LocalPoint::new(9.5, 3.0)), black);
+    }
+
+    #[test]
+    fn test_image_pattern() {
+        let paint = Paint::image_pattern(
+            LocalPoint::new(10.0, 20.0),
+            LocalSize::new(100.0, 50.0),
+            0.0,
+            0,
+            1.0,
+        );
+
+        let uv = |x: f32, y: f32| {
+            let p = paint.xform.transform_point(LocalPoint::new(x, y));
+            (
+                (p.x * 1000.0).round() / 1000.0,
+                (p.y * 1000.0).round() / 1000.0,
+            )
+        };
+
+        // First row of the image is at the top of the rect.
+        assert_eq!(uv(10.0, 70.0), (0.0, 0.0));
+        assert_eq!(uv(110.0, 70.0), (1.0, 0.0));
+        assert_eq!(uv(10.0, 20.0), (0.0, 1.0));
+        assert_eq!(uv(60.0, 45.0), (0.5, 0.5));
+    }
+
+    #[test]
+    fn test_with_mask() {
+        let start = LocalPoint::new(0.0, 0.0);
+        let end = LocalPoint::new(100.0, 0.0);
+        let paint = Paint::linear_gradient(start, end, Color::gray(0.0), Color::WHITE, 0.0)
+            .with_mask(
+                LocalPoint::new(10.0, 20.0),
+                LocalSize::new(100.0, 50.0),
+                0.0,
+                3,
+            );
+
+        assert_eq!(paint.image, 3);
+        assert_eq!(paint.mask, 1);
+
+        // The gradient is unchanged.
+        assert_eq!(paint.apply(LocalPoint::new(50.0, 0.0)).r, 0.5);
+
+        let p = paint
+            .mask_xform
+            .transform_point(LocalPoint::new(10.0, 70.0));
+        assert!(p.x.abs() < 1e-5 && p.y.abs() < 1e-5);
+    }
 }
@@ -4,12 +4,16 @@ pub const MAX_LAYERS: usize = 4;
 
 type Mat4x4 = [f32; 16];
 
+/// mat4x4 and offset, see `ColorMatrix::to_gpu`.
+pub(crate) type ColorMatrixData = [f32; 20];
+
 pub(crate) struct Scene {
     pub prims: [GPUVec<Prim>; MAX_LAYERS],
     pub cvs: GPUVec<LocalPoint>,
     pub xforms: GPUVec<Mat4x4>,
     pub paints: GPUVec<Paint>,
     pub scissors: GPUVec<Scissor>,
+    pub color_matrices: GPUVec<ColorMatrixData>,
     pub bind_groups: [wgpu::BindGroup; MAX_LAYERS],
 }
 
@@ -31,6 +35,7 @@ impl Scene {
         let xforms = GPUVec::new(device, INIT_PRIMS, "Xform Buffer");
         let paints = GPUVec::new(device, INIT_PRIMS, "Paint Buffer");
         let scissors = GPUVec::new(device, INIT_PRIMS, "scissor Buffer");
+        let color_matrices = GPUVec::new(device, INIT_PRIMS, "color matrix Buffer");
 
         let bind_group_layout = Self::bind_group_layout(device);
 
@@ -43,6 +48,7 @@ impl Scene {
                 &xforms,
                 &paints,
                 &scissors,
+                &color_matrices,
             )
         });
 
@@ -52,6 +58,7 @@ impl Scene {
             xforms,
             paints,
             scissors,
+            color_matrices,
             bind_groups,
         }
     }
@@ -64,11 +71,13 @@ impl Scene {
                 GPUVec::<Mat4x4>::bind_group_layout_entry(2),
                 GPUVec::<Paint>::bind_group_layout_entry(3),
                 GPUVec::<Scissor>::bind_group_layout_entry(4),
+                GPUVec::<ColorMatrixData>::bind_group_layout_entry(5),
             ],
             label: Some("BindGroupLayout for Scene"),
         })
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn bind_group(
         device: &wgpu::Device,
         bind_group_layout: &wgpu::BindGroupLayout,
@@ -77,6 +86,7 @@ impl Scene {
         xforms: &GPUVec<Mat4x4>,
         paints: &GPUVec<Paint>,
         scissors: &GPUVec<Scissor>,
+        color_matrices: &GPUVec<ColorMatrixData>,
     ) -> wgpu::BindGroup {
         device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: bind_group_layout,
@@ -86,6 +96,7 @@ impl Scene {
                 xforms.bind_group_entry(2),
                 paints.bind_group_entry(3),
                 scissors.bind_group_entry(4),
+                color_matrices.bind_group_entry(5),
             ],
             label: Some("vger bind group"),
         })
@@ -101,6 +112,7 @@ impl Scene {
         update_bind_groups |= self.xforms.update(device, queue);
         update_bind_groups |= self.paints.update(device, queue);
         update_bind_groups |= self.scissors.update(device, queue);
+        update_bind_groups |= self.color_matrices.update(device, queue);
 
         // If anything changed, regenerate all the bind groups.
         if update_bind_groups {
@@ -114,6 +126,7 @@ impl Scene {
                     &self.xforms,
                     &self.paints,
                     &self.scissors,
+                    &self.color_matrices,
                 );
             }
         }
@@ -127,5 +140,6 @@ impl Scene {
         self.xforms.clear();
         self.paints.clear();
         self.scissors.clear();
+        self.color_matrices.clear();
     }
 }
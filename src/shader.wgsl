@@ -512,6 +512,9 @@ struct VertexOutput {
 
     /// Point transformed by current transform.
     @location(2) p: vec2<f32>,
+
+    /// Local space point (before the current transform).
+    @location(3) q: vec2<f32>,
 };
 
 struct Uniforms {
@@ -557,6 +560,7 @@ fn vs_main(
         default: { }
     }
 
+    out.q = q;
     out.p = (xforms.xforms[prim.xform] * vec4<f32>(q, 0.0, 1.0)).xy;
     out.position = vec4<f32>(2.0 * out.p / uniforms.size - 1.0, 0.0, 1.0);
 
@@ -576,12 +580,21 @@ fn unpack_mat3x2(m: PackedMat3x2) -> mat3x2<f32> {
     return mat3x2<f32>(m.m11, m.m12, m.m21, m.m22, m.m31, m.m32);
 }
 
-struct Paint {              // align  size
-    xform: PackedMat3x2,    // 8      24
-    glow: f32,              // 4      4
-    image: i32,             // 4      4
-    inner_color: vec4<f32>, // 16     16
-    outer_color: vec4<f32>, // 16     16
+struct Paint {                   // align  size
+    xform: PackedMat3x2,         // 8      24
+    glow: f32,                   // 4      4
+    image: i32,                  // 4      4
+    inner_color: vec4<f32>,      // 16     16
+    outer_color: vec4<f32>,      // 16     16
+    dither: f32,                 // 4      4
+    kind: u32,                   // 4      4
+    image_generation: u32,       // 4      4
+    pad0: f32,                   // 4      4
+    params: array<vec4<f32>, 2>, // 16     32
+    image_rect: vec4<f32>,       // 16     16
+    mask_xform: PackedMat3x2,    // 8      24
+    mask: u32,                   // 4      4
+    color_matrix: i32,           // 4      4
 };
 
 struct Paints {
@@ -592,13 +605,129 @@ struct Paints {
 @binding(3)
 var<storage> paints: Paints;
 
-fn apply(paint: Paint, p: vec2<f32>) -> vec4<f32> {
+struct ColorMatrix {
+    m: mat4x4<f32>,
+    offset: vec4<f32>,
+};
+
+struct ColorMatrices {
+    matrices: array<ColorMatrix>,
+};
+
+@group(0)
+@binding(5)
+var<storage> color_matrices: ColorMatrices;
+
+// Applies the paint's color matrix, if any, to an image color.
+fn adjustColor(paint: Paint, color: vec4<f32>) -> vec4<f32> {
+    if paint.color_matrix < 0 {
+        return color;
+    }
+    let cm = color_matrices.matrices[paint.color_matrix];
+    return clamp(cm.m * color + cm.offset, vec4<f32>(0.0), vec4<f32>(1.0));
+}
+
+// Integral of a unit period pulse train which is 1 on [0, w).
+fn pulseIntegral(x: f32, w: f32) -> f32 {
+    return floor(x) * w + min(fract(x), w);
+}
+
+// Pulse train box filtered over a width of fw periods, so patterns
+// fade to their average instead of aliasing when minified.
+fn filteredPulse(x: f32, w: f32, fw: f32) -> f32 {
+    let xr = fract(x);
+    let h = 0.5 * max(fw, 0.0001);
+    return (pulseIntegral(xr + h, w) - pulseIntegral(xr - h, w)) / (2.0 * h);
+}
+
+/// Evaluates a paint at p, with fw being the filter width in the
+/// same (local) space.
+fn apply(paint: Paint, p: vec2<f32>, fw: f32) -> vec4<f32> {
     let local_point = unpack_mat3x2(paint.xform) * vec3<f32>(p, 1.0);
-    let d = clamp(local_point, vec2<f32>(0.0), vec2<f32>(1.0)).x;
+    var d = 0.0;
+
+    switch(paint.kind) {
+        case 1u: { // Box gradient.
+            let extent = paint.params[0].xy;
+            let radius = paint.params[0].z;
+            let feather = paint.params[0].w;
+            d = clamp((sdBox(local_point, extent, radius) + feather*0.5) / feather, 0.0, 1.0);
+        }
+        case 2u: { // Checkerboard.
+            let q = local_point / (2.0 * paint.params[0].x);
+            let w = fw / (2.0 * paint.params[0].x);
+            let sx = filteredPulse(q.x, 0.5, w);
+            let sy = filteredPulse(q.y, 0.5, w);
+            d = sx + sy - 2.0 * sx * sy;
+        }
+        case 3u: { // Stripes.
+            let x = local_point.x / (2.0 * paint.params[0].x);
+            d = 1.0 - filteredPulse(x, 0.5, fw / (2.0 * paint.params[0].x));
+        }
+        case 4u: { // Hatching.
+            let spacing = paint.params[0].x;
+            let w = paint.params[0].y / spacing;
+            let x = local_point.x / spacing + 0.5 * w;
+            d = 1.0 - filteredPulse(x, w, fw / spacing);
+        }
+        default: { // Linear gradient.
+            d = clamp(local_point, vec2<f32>(0.0), vec2<f32>(1.0)).x;
+        }
+    }
 
     return mix(paint.inner_color, paint.outer_color, d);
 }
 
+// Maps distance d along one axis of a nine-slice destination of
+// length len to a pixel coordinate in an image of length size, with
+// insets a and b at either end.
+fn nineSliceAxis(d: f32, len: f32, size: f32, a: f32, b: f32, tile: bool) -> f32 {
+    // Shrink the corners if the destination is too small for them.
+    let k = min(1.0, len / max(a + b, 0.0001));
+    if d < a * k {
+        return d / k;
+    }
+    if d > len - b * k {
+        return size - (len - d) / k;
+    }
+    let src = size - a - b;
+    let dst = len - a - b;
+    if tile {
+        return a + (d - a) % max(src, 0.0001);
+    }
+    return a + (d - a) * src / max(dst, 0.0001);
+}
+
+// Texture coordinates for an image paint.
+fn imageUV(paint: Paint, p: vec2<f32>) -> vec2<f32> {
+    if paint.mask != 0u {
+        let uv = unpack_mat3x2(paint.mask_xform) * vec3<f32>(p, 1.0);
+        return atlasUV(paint, uv);
+    }
+    var uv = unpack_mat3x2(paint.xform) * vec3<f32>(p, 1.0);
+    if paint.kind == 5u || paint.kind == 6u { // Nine-slice.
+        let len = paint.params[0].xy;
+        let size = paint.params[0].zw;
+        let insets = paint.params[1];
+        let tile = paint.kind == 6u;
+        let x = nineSliceAxis(uv.x, len.x, size.x, insets.x, insets.z, tile);
+        let y = nineSliceAxis(uv.y, len.y, size.y, insets.y, insets.w, tile);
+        uv = vec2<f32>(x, y) / size;
+    }
+    return atlasUV(paint, uv);
+}
+
+// Maps image uv to the image's rect in the image atlas, clamping to
+// the edge texels' centers so neighbors don't bleed in.
+fn atlasUV(paint: Paint, uv: vec2<f32>) -> vec2<f32> {
+    let size = paint.image_rect.zw;
+    if size.x == 0.0 {
+        return uv;
+    }
+    let texel = clamp(uv * size, vec2<f32>(0.5), size - 0.5);
+    return (paint.image_rect.xy + texel) / imageDimensions(paint);
+}
+
 struct Scissor {
     xform: PackedMat3x2,
     origin: vec2<f32>,
@@ -638,9 +767,18 @@ var glyph_atlas: texture_2d<f32>;
 @binding(2)
 var samp : sampler;
 
-@group(2)
-@binding(0)
-var tex : texture_2d<f32>;
+struct ImageInfo {
+    // 0 for RGBA images, 1 for NV12 and 2 for I420.
+    yuv: u32,
+    // Only used with image arrays, see image_array.wgsl.
+    generation: u32,
+    sampler_index: u32,
+    chroma: u32,
+    yuv_to_rgb: ColorMatrix,
+};
+
+// Image bindings, along with sampleImage and imageDimensions, come
+// from image_bindings.wgsl or image_array.wgsl.
 
 // sRGB to linear conversion for one channel.
 fn toLinear(s: f32) -> f32
@@ -651,6 +789,45 @@ fn toLinear(s: f32) -> f32
     return pow((s + 0.055)/1.055, 2.4);
 }
 
+// Linear to sRGB conversion for one channel.
+fn toSrgb(l: f32) -> f32
+{
+    if l < 0.0031308 {
+        return l*12.92;
+    }
+    return 1.055 * pow(l, 1.0/2.4) - 0.055;
+}
+
+// Converts gamma encoded (Y, U, V, 1) to linear RGBA.
+fn yuvToRgb(info: ImageInfo, yuv: vec4<f32>) -> vec4<f32> {
+    let cm = info.yuv_to_rgb;
+    let rgb = clamp(cm.m * yuv + cm.offset, vec4<f32>(0.0), vec4<f32>(1.0));
+    return vec4<f32>(toLinear(rgb.r), toLinear(rgb.g), toLinear(rgb.b), 1.0);
+}
+
+// 4x4 ordered dither threshold in (0, 1) for a pixel position.
+fn bayer4(p: vec2<u32>) -> f32 {
+    var m = array<f32, 16>(
+         0.0,  8.0,  2.0, 10.0,
+        12.0,  4.0, 14.0,  6.0,
+         3.0, 11.0,  1.0,  9.0,
+        15.0,  7.0, 13.0,  5.0
+    );
+    return (m[(p.y % 4u) * 4u + (p.x % 4u)] + 0.5) / 16.0;
+}
+
+// Perturbs a color by less than one 8-bit sRGB step to
+// break up banding in gradients.
+fn dither(c: vec4<f32>, frag: vec2<f32>) -> vec4<f32> {
+    let n = (bayer4(vec2<u32>(frag)) - 0.5) / 255.0;
+    return vec4<f32>(
+        toLinear(clamp(toSrgb(c.r) + n, 0.0, 1.0)),
+        toLinear(clamp(toSrgb(c.g) + n, 0.0, 1.0)),
+        toLinear(clamp(toSrgb(c.b) + n, 0.0, 1.0)),
+        c.a
+    );
+}
+
 // This approximates the error function, needed for the gaussian integral
 fn erf(x: vec2<f32>) -> vec2<f32> {
     let s = sign(x);
@@ -686,20 +863,41 @@ fn fs_main(
     // let a = textureSample(glyph_atlas, samp, (in.t+0.5)/1024.0).r;
     let a = textureLoad(glyph_atlas, vec2<i32>(in.t), 0).r;
 
+    // Glyphs use texture space for the atlas, so paints are
+    // evaluated at the local point instead.
+    var paint_point = in.t;
+    if(prim.prim_type == 8u) { // vgerGlyph
+        paint_point = in.q;
+    }
+    let paint_fw = length(fwidth(paint_point));
+
     // Look up image color (if no active image, still have to because of wgsl).
     // Note that we could use a separate shader if that's a perf hit.
-    let t = unpack_mat3x2(paint.xform) * vec3<f32>(in.t, 1.0);
-    var color = textureSample(tex, samp, t);
+    let t = imageUV(paint, paint_point);
+    let sampled = sampleImage(paint, t);
+
+    // Image paints tint the image with inner_color, which image
+    // patterns set to white with the paint's alpha.
+    var color = adjustColor(paint, sampled) * paint.inner_color;
+    if paint.image == -1 || paint.mask != 0u {
+        color = apply(paint, paint_point, paint_fw);
+    }
+
+    if paint.mask != 0u {
+        color.a *= sampled.r;
+    }
+
+    if paint.dither != 0.0 {
+        color = dither(color, in.position.xy);
+    }
 
     let s = scissor_sdf(scissor, in.p);
 
     if(prim.prim_type == 8u) { // vgerGlyph
 
-        let c = paint.inner_color;
-
         // XXX: using toLinear is a bit of a guess. Gets us closer
         // to matching the glyph atlas in the output.
-        var color = vec4<f32>(c.rgb, toLinear(a));
+        color.a *= toLinear(a);
 
         //if(glow) {
         //    color.a *= paint.glow;
@@ -711,9 +909,6 @@ fn fs_main(
     }
 
     let d = max(sdPrim(prim, in.t, fw), s);
-    if paint.image == -1 {
-        color = apply(paint, in.t);
-    }
 
     return mix(vec4<f32>(color.rgb,0.0), color, sdf_alpha(d, fw) );
 }
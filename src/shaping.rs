@@ -0,0 +1,185 @@
+//! OpenType shaping with rustybuzz, for `Vger::shaped_text`.
+
+use crate::defs::LocalRect;
+use crate::glyphs::GlyphCache;
+use crate::text_layout::{HorizontalAlign, TextLayoutOptions, VerticalAlign};
+use crate::FontId;
+use fontdue::layout::GlyphRasterConfig;
+use std::ops::Range;
+use unicode_bidi::BidiInfo;
+
+/// A glyph from `Vger::shaped_glyphs`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ShapedGlyph {
+    /// The font the glyph is from, which may be a fallback.
+    pub font: FontId,
+
+    /// Glyph index in the font.
+    pub glyph_id: u16,
+
+    /// Byte offset in the text of the first character the glyph draws.
+    /// Ligatures draw several characters and composed marks several
+    /// code points.
+    pub cluster: usize,
+
+    /// Bounds of the glyph's quad in local coordinates, as drawn with
+    /// no transform or a translation by whole device pixels. Other
+    /// translations can move edges by a pixel.
+    pub rect: LocalRect,
+}
+
+/// A shaped glyph in device pixels, positioned the way fontdue's layout
+/// positions glyphs.
+pub(crate) struct PositionedGlyph {
+    pub font: usize,
+    pub key: GlyphRasterConfig,
+    pub cluster: usize,
+
+    /// Pen position on the baseline.
+    pub pen: [f32; 2],
+}
+
+/// Shapes text in visual order, aligned like fontdue aligns text in a
+/// box with its top left at the origin. `scale` converts the options'
+/// sizes to device pixels. Each paragraph is a line: shaped text isn't
+/// wrapped, so justified lines are left aligned.
+pub(crate) fn shape(
+    cache: &GlyphCache,
+    text: &str,
+    font: usize,
+    px: f32,
+    options: TextLayoutOptions,
+    scale: f32,
+) -> Vec<PositionedGlyph> {
+    let align_x = match options.horizontal_align {
+        HorizontalAlign::Left | HorizontalAlign::Justify => 0.0,
+        HorizontalAlign::Center => 0.5,
+        HorizontalAlign::Right => 1.0,
+    };
+    let align_y = match options.vertical_align {
+        VerticalAlign::Top => 0.0,
+        VerticalAlign::Middle => 0.5,
+        VerticalAlign::Bottom => 1.0,
+    };
+
+    let bidi = BidiInfo::new(text, None);
+    let mut glyphs = vec![];
+    let mut baseline_y = 0.0;
+
+    for paragraph in &bidi.paragraphs {
+        let (levels, runs) = bidi.visual_runs(paragraph, paragraph.range.clone());
+        let line_start = glyphs.len();
+        let mut x = 0.0;
+
+        // Like fontdue, size the line for every font in it.
+        let mut line_fonts = vec![];
+        for run in runs {
+            let mut font_runs = cache.font_runs(&text[run.clone()], font);
+
+            // Each run is shaped in visual order, so right-to-left font
+            // runs are reversed.
+            let rtl = levels[run.start].is_rtl();
+            if rtl {
+                font_runs.reverse();
+            }
+
+            for (range, run_font) in font_runs {
+                let range = run.start + range.start..run.start + range.end;
+                shape_run(cache, text, range, run_font, rtl, px, &mut x, &mut glyphs);
+                line_fonts.push(run_font);
+            }
+        }
+        if line_fonts.is_empty() {
+            line_fonts.push(font);
+        }
+
+        let (ascent, new_line_size) = line_fonts
+            .iter()
+            .filter_map(|&f| cache.fonts[f].horizontal_line_metrics(px))
+            .fold((0.0f32, 0.0f32), |(ascent, size), metrics| {
+                (
+                    ascent.max(metrics.ascent.ceil()),
+                    size.max(metrics.new_line_size.ceil()),
+                )
+            });
+
+        let offset_x = match options.max_width {
+            Some(max_width) => ((max_width * scale - x) * align_x).floor(),
+            None => 0.0,
+        };
+
+        baseline_y -= ascent;
+        for glyph in &mut glyphs[line_start..] {
+            glyph.pen[0] += offset_x;
+            glyph.pen[1] += baseline_y;
+        }
+        baseline_y -= new_line_size - ascent;
+    }
+
+    // baseline_y is now the height of the text, below the origin.
+    if let Some(max_height) = options.max_height {
+        let offset_y = ((max_height * scale + baseline_y) * align_y).floor();
+        for glyph in &mut glyphs {
+            glyph.pen[1] -= offset_y;
+        }
+    }
+
+    glyphs
+}
+
+/// Shapes a run of text with one font and direction, appending glyphs
+/// relative to the baseline starting at `x`.
+#[allow(clippy::too_many_arguments)]
+fn shape_run(
+    cache: &GlyphCache,
+    text: &str,
+    range: Range<usize>,
+    font: usize,
+    rtl: bool,
+    px: f32,
+    x: &mut f32,
+    glyphs: &mut Vec<PositionedGlyph>,
+) {
+    // Fonts are checked when they're added.
+    let face = rustybuzz::Face::from_slice(&cache.font_data[font], 0).unwrap();
+    let fontdue_font = &cache.fonts[font];
+    let scale = px / face.units_per_em() as f32;
+
+    let mut buffer = rustybuzz::UnicodeBuffer::new();
+    buffer.push_str(&text[range.clone()]);
+    buffer.set_direction(if rtl {
+        rustybuzz::Direction::RightToLeft
+    } else {
+        rustybuzz::Direction::LeftToRight
+    });
+    buffer.guess_segment_properties();
+    let output = rustybuzz::shape(&face, &[], buffer);
+
+    for (info, position) in output.glyph_infos().iter().zip(output.glyph_positions()) {
+        let cluster = range.start + info.cluster as usize;
+        let c = text[cluster..].chars().next().unwrap();
+
+        // Like fontdue, line breaks, tabs and other control characters
+        // take no space.
+        if c.is_control() {
+            continue;
+        }
+
+        let glyph_index = info.glyph_id as u16;
+        glyphs.push(PositionedGlyph {
+            font,
+            key: GlyphRasterConfig {
+                glyph_index,
+                px,
+                font_hash: fontdue_font.file_hash(),
+            },
+            cluster,
+            pen: [
+                *x + position.x_offset as f32 * scale,
+                position.y_offset as f32 * scale,
+            ],
+        });
+
+        *x += position.x_advance as f32 * scale;
+    }
+}
@@ -0,0 +1,128 @@
+use fontdue::layout::{GlyphPosition, LayoutSettings, LinePosition};
+
+/// Horizontal alignment of lines within `TextLayoutOptions::max_width`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HorizontalAlign {
+    Left,
+    Center,
+    Right,
+
+    /// Stretch the spaces of wrapped lines so they fill the width. The
+    /// last line of each paragraph is left aligned.
+    Justify,
+}
+
+/// Vertical alignment of text within `TextLayoutOptions::max_height`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VerticalAlign {
+    Top,
+    Middle,
+    Bottom,
+}
+
+/// How text is laid out within a box whose top left is at the origin.
+/// Alignment needs the size of the box in that direction.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TextLayoutOptions {
+    /// Wrap lines longer than this.
+    pub max_width: Option<f32>,
+
+    /// Height to align text within. Longer text overflows it.
+    pub max_height: Option<f32>,
+
+    pub horizontal_align: HorizontalAlign,
+    pub vertical_align: VerticalAlign,
+}
+
+impl Default for TextLayoutOptions {
+    fn default() -> Self {
+        Self {
+            max_width: None,
+            max_height: None,
+            horizontal_align: HorizontalAlign::Left,
+            vertical_align: VerticalAlign::Top,
+        }
+    }
+}
+
+impl TextLayoutOptions {
+    /// Top left aligned text wrapped at `max_width`.
+    pub fn wrapped(max_width: f32) -> Self {
+        Self {
+            max_width: Some(max_width),
+            ..Self::default()
+        }
+    }
+
+    /// fontdue's settings, with sizes in device pixels.
+    pub(crate) fn layout_settings(&self, scale: f32) -> LayoutSettings {
+        LayoutSettings {
+            max_width: self.max_width.map(|w| w * scale),
+            max_height: self.max_height.map(|h| h * scale),
+            horizontal_align: match self.horizontal_align {
+                HorizontalAlign::Left | HorizontalAlign::Justify => {
+                    fontdue::layout::HorizontalAlign::Left
+                }
+                HorizontalAlign::Center => fontdue::layout::HorizontalAlign::Center,
+                HorizontalAlign::Right => fontdue::layout::HorizontalAlign::Right,
+            },
+            vertical_align: match self.vertical_align {
+                VerticalAlign::Top => fontdue::layout::VerticalAlign::Top,
+                VerticalAlign::Middle => fontdue::layout::VerticalAlign::Middle,
+                VerticalAlign::Bottom => fontdue::layout::VerticalAlign::Bottom,
+            },
+            ..LayoutSettings::default()
+        }
+    }
+}
+
+/// Spreads the words of wrapped lines laid out from x = 0 so their ink
+/// spans `max_width`, moving the glyphs' `pens` with them. Lines ending
+/// in a line break or ending the text are left alone.
+pub(crate) fn justify<U: Copy>(
+    glyphs: &mut [GlyphPosition<U>],
+    pens: &mut [[f32; 2]],
+    lines: &[LinePosition],
+    max_width: f32,
+) {
+    for line in lines.iter().take(lines.len().saturating_sub(1)) {
+        let range = line.glyph_start..=line.glyph_end;
+        let (line_glyphs, line_pens) = match (glyphs.get_mut(range.clone()), pens.get_mut(range)) {
+            (Some(line_glyphs), Some(line_pens)) => (line_glyphs, line_pens),
+            _ => continue,
+        };
+
+        // Trailing spaces don't count, but a line break does.
+        let end = match line_glyphs
+            .iter()
+            .rposition(|glyph| !glyph.parent.is_whitespace())
+        {
+            Some(end) => end,
+            None => continue,
+        };
+        if line_glyphs.last().unwrap().char_data.is_control() {
+            continue;
+        }
+
+        let last = &line_glyphs[end];
+        let extra = max_width - (last.x + last.width as f32);
+        let spaces = line_glyphs[..end]
+            .iter()
+            .filter(|glyph| glyph.parent.is_whitespace())
+            .count();
+        if spaces == 0 || extra <= 0.0 {
+            continue;
+        }
+
+        let mut spaces_before = 0;
+        for (glyph, pen) in line_glyphs[..=end].iter_mut().zip(line_pens) {
+            if glyph.parent.is_whitespace() {
+                spaces_before += 1;
+            }
+            // Whole pixels keep the glyph's quad in place under its pen.
+            let dx = (extra * spaces_before as f32 / spaces as f32).floor();
+            glyph.x += dx;
+            pen[0] += dx;
+        }
+    }
+}
@@ -3,6 +3,12 @@ use std::fs::File;
 use vger::*;
 
 pub async fn setup() -> (wgpu::Device, wgpu::Queue) {
+    setup_with_features(wgpu::Features::empty()).await
+}
+
+/// Like `setup`, also requesting whichever of `optional_features` the
+/// adapter supports.
+pub async fn setup_with_features(optional_features: wgpu::Features) -> (wgpu::Device, wgpu::Queue) {
     let instance_desc = wgpu::InstanceDescriptor::default();
 
     let instance = wgpu::Instance::new(instance_desc);
@@ -14,13 +20,21 @@ pub async fn setup() -> (wgpu::Device, wgpu::Queue) {
     let adapter_info = adapter.get_info();
     println!("Using {} ({:?})", adapter_info.name, adapter_info.backend);
 
+    // Optional features usually need more than the default limits.
+    let required_features = adapter.features() & optional_features;
+    let required_limits = if required_features.is_empty() {
+        wgpu::Limits::default()
+    } else {
+        adapter.limits()
+    };
+
     let trace_dir = std::env::var("WGPU_TRACE");
     adapter
         .request_device(
             &wgpu::DeviceDescriptor {
                 label: None,
-                required_features: wgpu::Features::default(),
-                required_limits: wgpu::Limits::default(),
+                required_features,
+                required_limits,
                 memory_hints: wgpu::MemoryHints::Performance,
             },
             trace_dir.ok().as_ref().map(std::path::Path::new),
@@ -103,7 +117,9 @@ fn get_texture_data(
         mapped_at_creation: false,
     });
 
-    assert!((texture_extent.width * bytes_per_pixel) % wgpu::COPY_BYTES_PER_ROW_ALIGNMENT == 0);
+    assert!(
+        (texture_extent.width * bytes_per_pixel).is_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+    );
 
     let command_buffer = {
         let mut encoder =
@@ -229,3 +245,11 @@ pub fn png_not_black(path: &str) -> bool {
 
     false
 }
+
+pub fn read_png(path: &str) -> Vec<u8> {
+    let decoder = png::Decoder::new(File::open(path).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut buf = vec![0; reader.output_buffer_size()];
+    reader.next_frame(&mut buf).unwrap();
+    buf
+}
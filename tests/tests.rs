@@ -30,6 +30,25 @@ fn test_color_hex() {
     assert_eq!(c.r, 0.0);
 }
 
+#[test]
+fn test_color_matrix() {
+    let red = Color::new(1.0, 0.0, 0.0, 1.0);
+    assert_eq!(ColorMatrix::IDENTITY.apply(red), red);
+
+    let gray = ColorMatrix::grayscale().apply(red);
+    assert!((gray.r - 0.213).abs() < 1e-6);
+    assert_eq!(gray.r, gray.g);
+    assert_eq!(gray.g, gray.b);
+    assert_eq!(gray.a, 1.0);
+
+    let m = ColorMatrix::grayscale().then(&ColorMatrix::brightness(2.0));
+    let c = m.apply(red);
+    assert!((c.r - 0.426).abs() < 1e-6);
+    assert_eq!(c.a, 1.0);
+
+    assert_eq!(ColorMatrix::saturation(1.0).apply(red), red);
+}
+
 #[test]
 fn fill_circle() {
     let (device, queue) = setup();
@@ -146,6 +165,178 @@ fn fill_rect_gradient() {
     render_test(&mut vger, &device, &queue, "rect_gradient.png", false);
 }
 
+/// Counts horizontally and vertically adjacent pixels in a row band that
+/// differ, which is low for smooth banded gradients.
+fn neighbor_changes(pixels: &[u8]) -> usize {
+    let px = |x: usize, y: usize| &pixels[(y * 512 + x) * 4..(y * 512 + x) * 4 + 3];
+    let mut changes = 0;
+    for y in 200..300 {
+        for x in 0..511 {
+            if px(x, y) != px(x + 1, y) {
+                changes += 1;
+            }
+            if px(x, y) != px(x, y + 1) {
+                changes += 1;
+            }
+        }
+    }
+    changes
+}
+
+#[test]
+fn fill_rect_gradient_dither() {
+    let (device, queue) = setup();
+
+    let mut changes = vec![];
+    for dither in [false, true] {
+        let mut vger = Vger::new(
+            device.clone(),
+            queue.clone(),
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+        );
+
+        vger.begin(512.0, 512.0, 1.0);
+
+        vger.set_gradient_dither(dither);
+
+        let paint = vger.linear_gradient(
+            [0.0, 0.0],
+            [512.0, 0.0],
+            Color::gray(0.2),
+            Color::gray(0.25),
+            0.0,
+        );
+
+        vger.fill_rect(euclid::rect(0.0, 0.0, 512.0, 512.0), 0.0, paint);
+
+        let png_name = if dither {
+            "rect_gradient_dither.png"
+        } else {
+            "rect_gradient_no_dither.png"
+        };
+        render_test(&mut vger, &device, &queue, png_name, false);
+        assert!(png_not_black(png_name));
+        changes.push(neighbor_changes(&read_png(png_name)));
+    }
+
+    // Without dithering, neighbours only differ at the edges of the few
+    // bands. Dithering breaks the bands up.
+    assert!(changes[1] > 10 * changes[0], "{:?}", changes);
+}
+
+#[test]
+fn box_gradient() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let paint = vger.box_gradient(
+        euclid::rect(150.0, 150.0, 200.0, 100.0),
+        20.0,
+        40.0,
+        Color::CYAN,
+        Color::MAGENTA,
+    );
+
+    vger.fill_rect(euclid::rect(100.0, 100.0, 300.0, 200.0), 0.0, paint);
+    vger.stroke_segment([100.0, 400.0], [400.0, 350.0], 20.0, paint);
+
+    let png_name = "box_gradient.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    let pixels = read_png(png_name);
+    let pixel = |x: usize, y: usize| {
+        let i = ((512 - y) * 512 + x) * 4;
+        [pixels[i], pixels[i + 1], pixels[i + 2]]
+    };
+
+    // Inside the box, past the feather, and on the box's edge halfway
+    // through the feather.
+    assert_eq!(pixel(250, 200), [0, 255, 255]);
+    assert_eq!(pixel(110, 110), [255, 0, 255]);
+    let [r, g, b] = pixel(350, 200);
+    assert_eq!(b, 255);
+    assert!((r as i32 - g as i32).abs() <= 8, "{:?}", [r, g, b]);
+    assert!((100..=220).contains(&r), "{:?}", [r, g, b]);
+}
+
+#[test]
+fn pattern_paints() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let checker = vger.checkerboard(8.0, 0.0, Color::gray(0.4), Color::gray(0.6));
+    vger.fill_rect(euclid::rect(20.0, 20.0, 150.0, 150.0), 10.0, checker);
+
+    let stripes = vger.stripes(10.0, 0.5, Color::CYAN, Color::MAGENTA);
+    vger.fill_rect(euclid::rect(190.0, 20.0, 300.0, 40.0), 20.0, stripes);
+
+    let hatch = vger.hatch(
+        12.0,
+        2.0,
+        std::f32::consts::FRAC_PI_4,
+        Color::WHITE,
+        Color::gray(0.2),
+    );
+    vger.fill_circle([350.0, 300.0], 100.0, hatch);
+
+    // Minified checkerboard should fade to gray rather than alias.
+    vger.save();
+    vger.translate([20.0, 300.0]);
+    vger.scale([0.05, 0.05]);
+    let tiny = vger.checkerboard(8.0, 0.3, Color::gray(0.0), Color::WHITE);
+    vger.fill_rect(euclid::rect(0.0, 0.0, 3000.0, 3000.0), 0.0, tiny);
+    vger.restore();
+
+    let png_name = "pattern_paints.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    let pixels = read_png(png_name);
+    let pixel = |x: usize, y: usize| {
+        let i = ((512 - y) * 512 + x) * 4;
+        [pixels[i], pixels[i + 1], pixels[i + 2]]
+    };
+
+    // Checker cells alternate every 8 pixels from the origin.
+    let dark = pixel(36, 36);
+    let light = pixel(44, 36);
+    assert!(dark[0] < light[0]);
+    for (x, y) in [(52, 36), (44, 44), (100, 148)] {
+        assert_eq!(pixel(x, y), dark, "{} {}", x, y);
+    }
+    for (x, y) in [(36, 44), (60, 36), (108, 148)] {
+        assert_eq!(pixel(x, y), light, "{} {}", x, y);
+    }
+
+    // Stripes are 10 wide across the rotated x axis, cyan first. Skip
+    // pixels near the edges of stripes.
+    let (sin, cos) = 0.5f32.sin_cos();
+    for x in 200..480 {
+        let u = (x as f32 + 0.5) * cos + 40.5 * sin;
+        let phase = (u / 20.0).fract();
+        if (0.1..0.4).contains(&phase) {
+            assert_eq!(pixel(x, 40), [0, 255, 255], "{}", x);
+        } else if (0.6..0.9).contains(&phase) {
+            assert_eq!(pixel(x, 40), [255, 0, 255], "{}", x);
+        }
+    }
+}
+
 #[test]
 fn stroke_rect_gradient() {
     let (device, queue) = setup();
@@ -326,13 +517,75 @@ fn text() {
     vger.begin(512.0, 512.0, 1.0);
 
     vger.translate([32.0, 256.0]);
-    vger.text("This is a test", 32, Color::WHITE, None);
+    vger.text(
+        "This is a test",
+        FontId::DEFAULT,
+        32.0,
+        Color::WHITE,
+        TextLayoutOptions::default(),
+    );
 
     let png_name = "text.png";
     render_test(&mut vger, &device, &queue, png_name, true);
     assert!(png_not_black(png_name));
 }
 
+#[test]
+fn text_gradient() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    vger.translate([32.0, 256.0]);
+    let paint = vger.linear_gradient([0.0, 0.0], [256.0, 0.0], Color::CYAN, Color::MAGENTA, 0.0);
+    vger.text_with_paint(
+        "This is a test",
+        FontId::DEFAULT,
+        32.0,
+        paint,
+        TextLayoutOptions::default(),
+    );
+
+    let png_name = "text_gradient.png";
+    render_test(&mut vger, &device, &queue, png_name, true);
+    assert!(png_not_black(png_name));
+
+    // The gradient runs in the text's local space from x = 0 to 256,
+    // so the fill goes from cyan toward magenta along the text. Fully
+    // covered pixels have full blue, and red and green from the
+    // gradient, mixed in linear space and stored as sRGB.
+    let pixels = read_png(png_name);
+    let covered: Vec<(usize, [u8; 3])> = pixels
+        .chunks(4)
+        .enumerate()
+        .filter(|(_, p)| p[2] == 255)
+        .map(|(i, p)| (i % 512, [p[0], p[1], p[2]]))
+        .collect();
+    let first = covered.iter().min_by_key(|(x, _)| *x).unwrap();
+    let last = covered.iter().max_by_key(|(x, _)| *x).unwrap();
+    assert!(first.0 < 48 && last.0 > 200, "{:?} {:?}", first, last);
+
+    let srgb = |c: f32| {
+        let c = if c <= 0.0031308 {
+            c * 12.92
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        };
+        (c * 255.0).round() as i32
+    };
+    for (x, [r, g, _]) in [first, last] {
+        let t = (*x as f32 + 0.5 - 32.0) / 256.0;
+        assert!((*r as i32 - srgb(t)).abs() <= 3, "{} {:?}", x, [r, g]);
+        assert!((*g as i32 - srgb(1.0 - t)).abs() <= 3, "{} {:?}", x, [r, g]);
+    }
+}
+
 #[test]
 fn text_small() {
     let (device, queue) = setup();
@@ -346,7 +599,13 @@ fn text_small() {
     vger.begin(512.0, 512.0, 1.0);
 
     vger.translate([32.0, 256.0]);
-    vger.text("53", 18, Color::WHITE, None);
+    vger.text(
+        "53",
+        FontId::DEFAULT,
+        18.0,
+        Color::WHITE,
+        TextLayoutOptions::default(),
+    );
 
     let png_name = "text_small.png";
     render_test(&mut vger, &device, &queue, png_name, true);
@@ -375,7 +634,13 @@ fn text_scale() {
     vger.begin(256.0, 256.0, 2.0);
 
     vger.translate([32.0, 128.0]);
-    vger.text("This is a test", 32, Color::WHITE, None);
+    vger.text(
+        "This is a test",
+        FontId::DEFAULT,
+        32.0,
+        Color::WHITE,
+        TextLayoutOptions::default(),
+    );
 
     let png_name = "text_scale.png";
     render_test(&mut vger, &device, &queue, png_name, true);
@@ -409,11 +674,22 @@ fn text_box() {
 
     vger.translate([32.0, 256.0]);
 
-    let bounds = vger.text_bounds(lorem, 18, Some(448.0));
+    let bounds = vger.text_bounds(
+        lorem,
+        FontId::DEFAULT,
+        18.0,
+        TextLayoutOptions::wrapped(448.0),
+    );
 
     vger.stroke_rect(bounds.origin, bounds.max(), 10.0, 4.0, paint);
 
-    vger.text(lorem, 18, Color::WHITE, Some(448.0));
+    vger.text(
+        lorem,
+        FontId::DEFAULT,
+        18.0,
+        Color::WHITE,
+        TextLayoutOptions::wrapped(448.0),
+    );
 
     let png_name = "text_box.png";
     render_test(&mut vger, &device, &queue, png_name, true);
@@ -430,7 +706,7 @@ fn text_box() {
 }
 
 #[test]
-fn test_scissor() {
+fn custom_font() {
     let (device, queue) = setup();
 
     let mut vger = Vger::new(
@@ -439,19 +715,60 @@ fn test_scissor() {
         wgpu::TextureFormat::Rgba8UnormSrgb,
     );
 
-    vger.begin(512.0, 512.0, 2.0);
+    assert!(matches!(
+        vger.add_font(b"not a font"),
+        Err(FontError::Invalid(_))
+    ));
+    let atkinson = vger
+        .add_font(include_bytes!(
+            "../src/fonts/Atkinson-Hyperlegible-Regular-102.ttf"
+        ))
+        .unwrap();
+    assert_ne!(atkinson, FontId::DEFAULT);
+
+    let text = "Hamburgefonstiv";
+    let default_bounds =
+        vger.text_bounds(text, FontId::DEFAULT, 32.0, TextLayoutOptions::default());
+    let atkinson_bounds = vger.text_bounds(text, atkinson, 32.0, TextLayoutOptions::default());
+    assert_ne!(default_bounds, atkinson_bounds);
+    assert_eq!(
+        vger.glyph_positions(text, atkinson, 32.0, TextLayoutOptions::default())
+            .len(),
+        text.chars().count()
+    );
+    assert_eq!(
+        vger.line_metrics(text, atkinson, 32.0, TextLayoutOptions::default())
+            .len(),
+        1
+    );
 
-    vger.scissor(euclid::rect(200.0, 200.0, 100.0, 100.0));
-    let cyan = vger.color_paint(Color::WHITE);
-    vger.fill_rect(euclid::rect(100.0, 100.0, 300.0, 300.0), 10.0, cyan);
+    vger.begin(512.0, 512.0, 1.0);
 
-    let png_name = "scissor.png";
+    // The same glyphs at the same size, cached once per font.
+    vger.translate([32.0, 300.0]);
+    vger.text(
+        text,
+        FontId::DEFAULT,
+        32.0,
+        Color::WHITE,
+        TextLayoutOptions::default(),
+    );
+    vger.translate([0.0, -100.0]);
+    vger.text(
+        text,
+        atkinson,
+        32.0,
+        Color::WHITE,
+        TextLayoutOptions::default(),
+    );
+
+    let png_name = "custom_font.png";
     render_test(&mut vger, &device, &queue, png_name, true);
     assert!(png_not_black(png_name));
 }
 
 #[test]
-fn test_rounded_scissor() {
+fn font_fallbacks() {
     let (device, queue) = setup();
 
     let mut vger = Vger::new(
@@ -460,19 +777,57 @@ fn test_rounded_scissor() {
         wgpu::TextureFormat::Rgba8UnormSrgb,
     );
 
-    vger.begin(512.0, 512.0, 2.0);
+    // The built-in font has no π, so it comes from the fallback font.
+    let widths = |vger: &mut Vger, font| {
+        vger.glyph_positions("Wπ", font, 32.0, TextLayoutOptions::default())
+            .iter()
+            .map(|rect| rect.size.width)
+            .collect::<Vec<_>>()
+    };
+    let default_widths = widths(&mut vger, FontId::DEFAULT);
+    let fallback_widths = widths(&mut vger, FontId::FALLBACK);
+    assert_eq!(default_widths.len(), 2);
+    assert_ne!(default_widths[0], fallback_widths[0]);
+    assert_eq!(default_widths[1], fallback_widths[1]);
 
-    vger.rounded_scissor(euclid::rect(200.0, 200.0, 100.0, 100.0), 20.0);
-    let cyan = vger.color_paint(Color::WHITE);
-    vger.fill_rect(euclid::rect(100.0, 100.0, 300.0, 300.0), 10.0, cyan);
+    vger.begin(512.0, 512.0, 1.0);
+    vger.translate([32.0, 300.0]);
+    vger.text(
+        "2πr = τr",
+        FontId::DEFAULT,
+        32.0,
+        Color::WHITE,
+        TextLayoutOptions::default(),
+    );
 
-    let png_name = "rounded_scissor.png";
+    // Without fallbacks π is the built-in font's missing glyph.
+    vger.set_font_fallbacks(FontId::DEFAULT, &[]);
+    let missing_widths = widths(&mut vger, FontId::DEFAULT);
+    assert_eq!(missing_widths[0], default_widths[0]);
+    assert_ne!(missing_widths[1], fallback_widths[1]);
+    vger.translate([0.0, -100.0]);
+    vger.text(
+        "2πr = τr",
+        FontId::DEFAULT,
+        32.0,
+        Color::WHITE,
+        TextLayoutOptions::default(),
+    );
+
+    // Fallbacks apply to added fonts too.
+    let custom = vger
+        .add_font(include_bytes!("../src/fonts/Anodina-Regular.ttf"))
+        .unwrap();
+    vger.set_font_fallbacks(custom, &[FontId::FALLBACK]);
+    assert_eq!(widths(&mut vger, custom), default_widths);
+
+    let png_name = "font_fallbacks.png";
     render_test(&mut vger, &device, &queue, png_name, true);
     assert!(png_not_black(png_name));
 }
 
 #[test]
-fn test_scissor_text() {
+fn text_control_and_combining() {
     let (device, queue) = setup();
 
     let mut vger = Vger::new(
@@ -481,28 +836,117 @@ fn test_scissor_text() {
         wgpu::TextureFormat::Rgba8UnormSrgb,
     );
 
+    // Combining acute accents, a tab and line breaks.
+    let text = "Cafe\u{301}\tnai\u{308}ve\r\nre\u{301}sume\u{301}\n";
+    let rects = vger.glyph_positions(text, FontId::DEFAULT, 32.0, TextLayoutOptions::default());
+    assert_eq!(rects.len(), text.chars().count());
+    for (rect, c) in rects.iter().zip(text.chars()) {
+        if c.is_control() {
+            assert!(rect.is_empty());
+        }
+    }
+    assert_eq!(
+        vger.line_metrics(text, FontId::DEFAULT, 32.0, TextLayoutOptions::default())
+            .len(),
+        2
+    );
+
     vger.begin(512.0, 512.0, 1.0);
+    vger.translate([32.0, 400.0]);
+    vger.text(
+        text,
+        FontId::DEFAULT,
+        32.0,
+        Color::WHITE,
+        TextLayoutOptions::default(),
+    );
 
-    let paint = vger.linear_gradient([0.0, 0.0], [512.0, 512.0], Color::CYAN, Color::MAGENTA, 0.0);
+    let png_name = "text_control_and_combining.png";
+    render_test(&mut vger, &device, &queue, png_name, true);
+    assert!(png_not_black(png_name));
+}
 
-    let lorem = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna aliqua. Ut enim ad minim veniam, quis nostrud exercitation ullamco laboris nisi ut aliquip ex ea commodo consequat. Duis aute irure dolor in reprehenderit in voluptate velit esse cillum dolore eu fugiat nulla pariatur. Excepteur sint occaecat cupidatat non proident, sunt in culpa qui officia deserunt mollit anim id est laborum.";
+#[test]
+fn line_metrics_bounds() {
+    let (device, queue) = setup();
 
-    vger.translate([32.0, 256.0]);
-    vger.scissor(euclid::rect(-100.0, -100.0, 400.0, 400.0));
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
 
-    let bounds = vger.text_bounds(lorem, 18, Some(448.0));
+    let text = "Wrapped lines end in W\nand in M";
+    let options = TextLayoutOptions::wrapped(150.0);
+    let rects = vger.glyph_positions(text, FontId::DEFAULT, 24.0, options);
+    let lines = vger.line_metrics(text, FontId::DEFAULT, 24.0, options);
+    assert!(lines.len() > 2);
+
+    // Each line's bounds include its last visible glyph, and the last
+    // line's glyph is the text's last.
+    for line in &lines {
+        assert!(line.glyph_end < rects.len());
+        let last = (line.glyph_start..=line.glyph_end)
+            .rev()
+            .map(|i| rects[i])
+            .find(|rect| !rect.is_empty())
+            .unwrap();
+        assert!(line.bounds.contains_rect(&last), "{:?} {:?}", line, last);
+    }
+    assert_eq!(lines.last().unwrap().glyph_end, rects.len() - 1);
+    assert_eq!(
+        lines.last().unwrap().bounds.max_x(),
+        rects[rects.len() - 1].max_x()
+    );
 
-    vger.stroke_rect(bounds.origin, bounds.max(), 10.0, 4.0, paint);
+    let lines = vger.line_metrics("", FontId::DEFAULT, 24.0, options);
+    assert!(lines.iter().all(|line| line.bounds.is_empty()));
+}
 
-    vger.text(lorem, 18, Color::WHITE, Some(448.0));
+#[test]
+fn fractional_text_size() {
+    let (device, queue) = setup();
 
-    let png_name = "text_box_scissor.png";
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    let width = |vger: &mut Vger, size| {
+        vger.text_bounds("Zoom", FontId::DEFAULT, size, TextLayoutOptions::default())
+            .size
+            .width
+    };
+    let small = width(&mut vger, 26.0);
+    let fractional = width(&mut vger, 27.5);
+    let large = width(&mut vger, 29.0);
+    assert!(small < fractional && fractional < large);
+
+    // Nearby sizes share glyphs.
+    assert_eq!(width(&mut vger, 40.01), width(&mut vger, 40.02));
+
+    vger.begin(512.0, 512.0, 1.0);
+    vger.translate([32.0, 450.0]);
+    for i in 0..8 {
+        let size = 13.5 + i as f32 * 2.25;
+        vger.text(
+            "Zoom",
+            FontId::DEFAULT,
+            size,
+            Color::WHITE,
+            TextLayoutOptions::default(),
+        );
+        vger.translate([0.0, -size * 1.5]);
+    }
+
+    let png_name = "fractional_text_size.png";
     render_test(&mut vger, &device, &queue, png_name, true);
     assert!(png_not_black(png_name));
 }
 
 #[test]
-fn segment_stroke_stress() {
+fn subpixel_text() {
     let (device, queue) = setup();
 
     let mut vger = Vger::new(
@@ -511,29 +955,73 @@ fn segment_stroke_stress() {
         wgpu::TextureFormat::Rgba8UnormSrgb,
     );
 
-    vger.begin(512.0, 512.0, 1.0);
+    let mut render = |text: &str, x: f32| {
+        vger.begin(512.0, 512.0, 1.0);
+        vger.translate([x, 256.3]);
+        vger.text(
+            text,
+            FontId::DEFAULT,
+            13.0,
+            Color::WHITE,
+            TextLayoutOptions::default(),
+        );
+        let png_name = format!("subpixel_text_{}_{}.png", text.len(), x);
+        render_test(&mut vger, &device, &queue, &png_name, false);
+        read_png(&png_name)
+    };
+
+    let text = "illicit minimum";
+    let whole = render(text, 32.0);
+    let quarter = render(text, 32.25);
+    let half = render(text, 32.5);
+    let shifted = render(text, 33.0);
+
+    // Pens round to a quarter pixel, and glyphs land on whole pixels.
+    // The first glyph's pen is at the origin.
+    assert!(render("i", 32.25) == render("i", 32.3));
+    assert!(whole[..whole.len() - 4] == shifted[4..]);
+
+    // Each phase is rasterized rather than blurred from another, so the
+    // ink is the same.
+    assert!(quarter != whole && half != whole);
+    let ink = |pixels: &[u8]| pixels.iter().map(|&p| p as u32).sum::<u32>() as f32;
+    for pixels in [&quarter, &half].iter() {
+        assert!((ink(pixels) / ink(&whole) - 1.0).abs() < 0.03);
+    }
+}
 
-    let paint = vger.linear_gradient([0.0, 0.0], [512.0, 512.0], Color::CYAN, Color::MAGENTA, 0.0);
+#[test]
+fn text_spacing_unrounded() {
+    let (device, queue) = setup();
 
-    for _ in 0..100000 {
-        let mut rng = rand::thread_rng();
-        let a = rand2(&mut rng);
-        let b = rand2(&mut rng);
+    let mut vger = Vger::new(device, queue, wgpu::TextureFormat::Rgba8UnormSrgb);
 
-        vger.stroke_segment(a, b, 4.0, paint);
-    }
+    // At 14px an "i" is a fraction of a pixel past a whole number of
+    // pixels wide. Rounding each advance up would add that fraction for
+    // every glyph.
+    let advance = vger.glyph_cache.fonts[0].metrics('i', 14.0).advance_width;
+    let rounded = advance.ceil() - advance;
+    assert!(rounded > 0.25 && rounded < 0.75, "advance {}", advance);
 
-    render_test(
-        &mut vger,
-        &device,
-        &queue,
-        "segment_stroke_stress.png",
-        false,
+    let rects = vger.glyph_positions(
+        &"i".repeat(21),
+        FontId::DEFAULT,
+        14.0,
+        TextLayoutOptions::default(),
     );
+    let span = rects[20].origin.x - rects[0].origin.x;
+    assert!((span - 20.0 * advance).abs() <= 1.0, "span {}", span);
+
+    // Quads land on whole pixels, so the gaps between glyphs differ.
+    let gaps: Vec<f32> = rects
+        .windows(2)
+        .map(|pair| pair[1].origin.x - pair[0].origin.x)
+        .collect();
+    assert!(gaps.iter().any(|&gap| gap != gaps[0]));
 }
 
 #[test]
-fn segment_stroke_vertical() {
+fn glyph_positions_match_ink() {
     let (device, queue) = setup();
 
     let mut vger = Vger::new(
@@ -542,29 +1030,168 @@ fn segment_stroke_vertical() {
         wgpu::TextureFormat::Rgba8UnormSrgb,
     );
 
+    let text = "Wave illicit";
+    let options = TextLayoutOptions::default();
+    let rects = vger.glyph_positions(text, FontId::DEFAULT, 24.0, options);
+    let bounds = vger.text_bounds(text, FontId::DEFAULT, 24.0, options);
+    let rect_bounds = rects[1..]
+        .iter()
+        .fold(rects[0], |bounds, rect| bounds.union(rect));
+    assert_eq!(bounds, rect_bounds);
+
+    // Any translation by whole pixels draws the glyphs' quads where the
+    // positions say.
     vger.begin(512.0, 512.0, 1.0);
+    vger.translate([32.0, 256.0]);
+    vger.text(text, FontId::DEFAULT, 24.0, Color::WHITE, options);
+    let png_name = "glyph_positions_ink.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+
+    // Local space is y-up, so the first row of the image is at the top.
+    let pixels = read_png(png_name);
+    let mut ink = [f32::MAX, f32::MAX, f32::MIN, f32::MIN];
+    for (i, pixel) in pixels.chunks(4).enumerate() {
+        if pixel[0] > 0 {
+            let (x, y) = ((i % 512) as f32, (511 - i / 512) as f32);
+            ink = [
+                ink[0].min(x),
+                ink[1].min(y),
+                ink[2].max(x + 1.0),
+                ink[3].max(y + 1.0),
+            ];
+        }
+    }
+    assert_eq!(
+        ink,
+        [
+            bounds.min_x() + 32.0,
+            bounds.min_y() + 256.0,
+            bounds.max_x() + 32.0,
+            bounds.max_y() + 256.0
+        ]
+    );
+}
 
-    let paint = vger.linear_gradient(
-        [100.0, 100.0],
-        [100.0, 200.0],
-        Color::CYAN,
-        Color::MAGENTA,
-        0.0,
+#[test]
+fn text_alignment() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
     );
 
-    vger.stroke_segment([100.0, 100.0], [100.0, 200.0], 4.0, paint);
+    let boxed = |horizontal_align, vertical_align| TextLayoutOptions {
+        max_width: Some(400.0),
+        max_height: Some(200.0),
+        horizontal_align,
+        vertical_align,
+    };
+
+    // The box's top left is at the origin, so it spans y = -200 to 0.
+    let top_left = vger.text_bounds("Label", FontId::DEFAULT, 32.0, TextLayoutOptions::default());
+    let centered = vger.text_bounds(
+        "Label",
+        FontId::DEFAULT,
+        32.0,
+        boxed(HorizontalAlign::Center, VerticalAlign::Middle),
+    );
+    assert_eq!(centered.size, top_left.size);
+    assert!((centered.center().x - 200.0).abs() < 4.0);
+    assert!((centered.center().y + 100.0).abs() < 8.0);
+
+    let bottom_right = vger.text_bounds(
+        "Label",
+        FontId::DEFAULT,
+        32.0,
+        boxed(HorizontalAlign::Right, VerticalAlign::Bottom),
+    );
+    assert!(bottom_right.max_x() <= 400.0 && bottom_right.max_x() > 390.0);
+    assert!(bottom_right.min_y() >= -200.0 && bottom_right.min_y() < -190.0);
+
+    // Justified lines end at the edge, except the last of a paragraph.
+    let lorem =
+        "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore.\nDone.";
+    let justified = boxed(HorizontalAlign::Justify, VerticalAlign::Top);
+    let rects = vger.glyph_positions(lorem, FontId::DEFAULT, 18.0, justified);
+    let lines = vger.line_metrics(lorem, FontId::DEFAULT, 18.0, justified);
+    assert!(lines.len() > 3);
+    let right_edges: Vec<f32> = lines
+        .iter()
+        .map(|line| {
+            rects[line.glyph_start..=line.glyph_end]
+                .iter()
+                .map(|rect| rect.max_x())
+                .fold(0.0, f32::max)
+        })
+        .collect();
+    let (wrapped, paragraph_ends) = right_edges.split_at(lines.len() - 2);
+    for edge in wrapped {
+        assert_eq!(*edge, 400.0);
+    }
+    for edge in paragraph_ends {
+        assert!(*edge < 390.0);
+    }
 
-    render_test(
-        &mut vger,
-        &device,
-        &queue,
-        "segment_stroke_vertical.png",
-        false,
+    vger.begin(512.0, 512.0, 1.0);
+    vger.translate([56.0, 456.0]);
+    let paint = vger.color_paint(Color::hex("#333333").unwrap());
+    vger.fill_rect(euclid::rect(0.0, -200.0, 400.0, 200.0), 0.0, paint);
+    vger.text(
+        "Centered",
+        FontId::DEFAULT,
+        32.0,
+        Color::WHITE,
+        boxed(HorizontalAlign::Center, VerticalAlign::Middle),
     );
+    vger.translate([0.0, -220.0]);
+    vger.text(lorem, FontId::DEFAULT, 18.0, Color::WHITE, justified);
+
+    let png_name = "text_alignment.png";
+    render_test(&mut vger, &device, &queue, png_name, true);
+    assert!(png_not_black(png_name));
 }
 
+#[cfg(feature = "shaping")]
 #[test]
-fn segment_stroke_horizontal() {
+fn shaped_text_alignment() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    let options = TextLayoutOptions {
+        max_width: Some(400.0),
+        max_height: Some(200.0),
+        horizontal_align: HorizontalAlign::Right,
+        vertical_align: VerticalAlign::Middle,
+    };
+
+    // Lines align like the unshaped layout's. Horizontal positions
+    // differ a little since the layout rounds advances up.
+    let text = "Label\nline";
+    let shaped = vger.shaped_glyphs(text, FontId::DEFAULT, 32.0, options);
+    let unshaped: Vec<LocalRect> = vger
+        .glyph_positions(text, FontId::DEFAULT, 32.0, options)
+        .into_iter()
+        .filter(|rect| !rect.is_empty())
+        .collect();
+    assert_eq!(shaped.len(), unshaped.len());
+    for (glyph, rect) in shaped.iter().zip(&unshaped) {
+        assert_eq!(glyph.rect.origin.y, rect.origin.y);
+    }
+    for line in [&shaped[..5], &shaped[5..]].iter() {
+        let right = line.iter().map(|g| g.rect.max_x()).fold(0.0, f32::max);
+        assert!(right <= 400.0 && right > 390.0);
+    }
+}
+
+#[test]
+fn attributed_text() {
     let (device, queue) = setup();
 
     let mut vger = Vger::new(
@@ -575,21 +1202,2135 @@ fn segment_stroke_horizontal() {
 
     vger.begin(512.0, 512.0, 1.0);
 
-    let paint = vger.linear_gradient(
-        [100.0, 100.0],
-        [200.0, 100.0],
-        Color::CYAN,
-        Color::MAGENTA,
-        0.0,
+    let paint = vger.linear_gradient([0.0, 0.0], [400.0, 0.0], Color::CYAN, Color::MAGENTA, 0.0);
+    let text = AttributedText::new()
+        .with("Plain, ", FontId::DEFAULT, 24.0, Color::WHITE)
+        .with("big", FontId::FALLBACK, 48.0, Color::CYAN)
+        .with(" and a painted link", FontId::DEFAULT, 24.0, paint);
+    assert_eq!(text.text(), "Plain, big and a painted link");
+
+    // One line as tall as the big span.
+    let bounds = vger.attributed_text_bounds(&text, TextLayoutOptions::default());
+    let big_bounds = vger.text_bounds("big", FontId::FALLBACK, 48.0, TextLayoutOptions::default());
+    let plain_bounds = vger.text_bounds(
+        "Plain, ",
+        FontId::DEFAULT,
+        24.0,
+        TextLayoutOptions::default(),
+    );
+    assert!(bounds.size.height >= big_bounds.size.height);
+    assert!(bounds.size.width > plain_bounds.size.width + big_bounds.size.width);
+    let lines = vger.attributed_line_metrics(&text, TextLayoutOptions::default());
+    assert_eq!(lines.len(), 1);
+
+    // Wrapping breaks across spans, keeping every glyph.
+    let lines = vger.attributed_line_metrics(&text, TextLayoutOptions::wrapped(200.0));
+    assert!(lines.len() > 1);
+    assert_eq!(
+        lines.last().unwrap().glyph_end + 1,
+        text.text().chars().count()
     );
 
-    vger.stroke_segment([100.0, 100.0], [200.0, 100.0], 4.0, paint);
+    vger.translate([32.0, 300.0]);
+    vger.attributed_text(&text, TextLayoutOptions::wrapped(200.0));
 
-    render_test(
-        &mut vger,
-        &device,
-        &queue,
-        "segment_stroke_horizontal.png",
-        false,
+    let png_name = "attributed_text.png";
+    render_test(&mut vger, &device, &queue, png_name, true);
+    assert!(png_not_black(png_name));
+
+    // The big span is cyan and the others white or painted.
+    let pixels = read_png(png_name);
+    let count = |f: &dyn Fn(&[u8]) -> bool| pixels.chunks(4).filter(|p| f(p)).count();
+    assert!(count(&|p| p[0] == 0 && p[1] == 255 && p[2] == 255) > 0);
+    assert!(count(&|p| p[0] == 255 && p[1] == 255 && p[2] == 255) > 0);
+}
+
+#[cfg(feature = "shaping")]
+#[test]
+fn shaping_golden() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    let ids = |glyphs: &[ShapedGlyph]| glyphs.iter().map(|g| g.glyph_id).collect::<Vec<_>>();
+    let clusters = |glyphs: &[ShapedGlyph]| glyphs.iter().map(|g| g.cluster).collect::<Vec<_>>();
+
+    // Anodina kerns "AV" by 140 units, which fontdue's layout ignores,
+    // so V moves 14px.
+    let shaped = vger.shaped_glyphs("AV", FontId::DEFAULT, 100.0, TextLayoutOptions::default());
+    assert_eq!(ids(&shaped), [36, 57]);
+    assert_eq!(clusters(&shaped), [0, 1]);
+    let unshaped = vger.glyph_positions("AV", FontId::DEFAULT, 100.0, TextLayoutOptions::default());
+    assert_eq!(shaped[0].rect, unshaped[0]);
+    assert_eq!(unshaped[1].origin.x - shaped[1].rect.origin.x, 14.0);
+
+    // A combining acute accent composes with its base.
+    let shaped = vger.shaped_glyphs(
+        "e\u{301}",
+        FontId::DEFAULT,
+        32.0,
+        TextLayoutOptions::default(),
+    );
+    assert_eq!(ids(&shaped), [163]);
+    assert_eq!(clusters(&shaped), [0]);
+
+    // Hebrew runs right to left within a left-to-right paragraph.
+    let shaped = vger.shaped_glyphs(
+        "abc \u{5d0}\u{5d1}\u{5d2} def",
+        FontId::DEFAULT,
+        32.0,
+        TextLayoutOptions::default(),
+    );
+    assert_eq!(clusters(&shaped), [0, 1, 2, 3, 8, 6, 4, 10, 11, 12, 13]);
+    for pair in shaped.windows(2) {
+        assert!(pair[0].rect.origin.x < pair[1].rect.origin.x);
+    }
+
+    // Characters the font lacks come from its fallbacks.
+    let shaped = vger.shaped_glyphs(
+        "2\u{3c0}",
+        FontId::DEFAULT,
+        32.0,
+        TextLayoutOptions::default(),
+    );
+    assert_eq!(shaped[0].font, FontId::DEFAULT);
+    assert_eq!(shaped[1].font, FontId::FALLBACK);
+
+    // Line breaks draw nothing and start new lines, placed like the
+    // unshaped layout's.
+    let shaped = vger.shaped_glyphs(
+        "ab\ncd",
+        FontId::DEFAULT,
+        32.0,
+        TextLayoutOptions::default(),
+    );
+    assert_eq!(clusters(&shaped), [0, 1, 3, 4]);
+    let unshaped = vger.glyph_positions(
+        "ab\ncd",
+        FontId::DEFAULT,
+        32.0,
+        TextLayoutOptions::default(),
+    );
+    for (glyph, rect) in shaped.iter().zip(unshaped.iter().filter(|r| !r.is_empty())) {
+        assert_eq!(glyph.rect.origin.y, rect.origin.y);
+    }
+}
+
+#[cfg(feature = "shaping")]
+#[test]
+fn shaping_arabic() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    // DejaVu Sans Mono has Arabic's joining forms, which the default
+    // fonts lack.
+    let font = vger.add_font(include_bytes!("DejaVuSansMono.ttf")).unwrap();
+    let face = vger.glyph_cache.fonts.last().unwrap().clone();
+    let form = |c| face.lookup_glyph_index(c);
+
+    // Beh, yeh, teh join into initial, medial and final forms, laid out
+    // right to left. Each letter is two bytes of UTF-8.
+    let shaped = vger.shaped_glyphs(
+        "\u{628}\u{64a}\u{62a}",
+        font,
+        32.0,
+        TextLayoutOptions::default(),
     );
+    let ids = shaped.iter().map(|g| g.glyph_id).collect::<Vec<_>>();
+    let clusters = shaped.iter().map(|g| g.cluster).collect::<Vec<_>>();
+    assert_eq!(ids, [form('\u{fe96}'), form('\u{fef4}'), form('\u{fe91}')]);
+    assert_ne!(ids, [form('\u{62a}'), form('\u{64a}'), form('\u{628}')]);
+    assert_eq!(clusters, [4, 2, 0]);
+    assert!(shaped.iter().all(|g| g.font == font));
+    for pair in shaped.windows(2) {
+        assert!(pair[0].rect.origin.x < pair[1].rect.origin.x);
+    }
+
+    // Lam and alef form a single ligature glyph.
+    let shaped = vger.shaped_glyphs("\u{644}\u{627}", font, 32.0, TextLayoutOptions::default());
+    assert_eq!(shaped.len(), 1);
+    assert_eq!(shaped[0].glyph_id, form('\u{fefb}'));
+    assert_eq!(shaped[0].cluster, 0);
+}
+
+#[cfg(feature = "shaping")]
+#[test]
+fn shaped_text() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    vger.translate([32.0, 400.0]);
+    vger.shaped_text(
+        "AVA Te\u{301}",
+        FontId::DEFAULT,
+        48.0,
+        Color::WHITE,
+        TextLayoutOptions::default(),
+    );
+    vger.translate([0.0, -100.0]);
+    vger.text(
+        "AVA Te\u{301}",
+        FontId::DEFAULT,
+        48.0,
+        Color::WHITE,
+        TextLayoutOptions::default(),
+    );
+    vger.translate([0.0, -100.0]);
+    vger.shaped_text(
+        "2\u{3c0}r\nabc \u{5d0}\u{5d1} def",
+        FontId::DEFAULT,
+        32.0,
+        Color::WHITE,
+        TextLayoutOptions::default(),
+    );
+
+    let png_name = "shaped_text.png";
+    render_test(&mut vger, &device, &queue, png_name, true);
+    assert!(png_not_black(png_name));
+}
+
+#[test]
+fn test_scissor() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    vger.begin(512.0, 512.0, 2.0);
+
+    vger.scissor(euclid::rect(200.0, 200.0, 100.0, 100.0));
+    let cyan = vger.color_paint(Color::WHITE);
+    vger.fill_rect(euclid::rect(100.0, 100.0, 300.0, 300.0), 10.0, cyan);
+
+    let png_name = "scissor.png";
+    render_test(&mut vger, &device, &queue, png_name, true);
+    assert!(png_not_black(png_name));
+}
+
+#[test]
+fn test_rounded_scissor() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    vger.begin(512.0, 512.0, 2.0);
+
+    vger.rounded_scissor(euclid::rect(200.0, 200.0, 100.0, 100.0), 20.0);
+    let cyan = vger.color_paint(Color::WHITE);
+    vger.fill_rect(euclid::rect(100.0, 100.0, 300.0, 300.0), 10.0, cyan);
+
+    let png_name = "rounded_scissor.png";
+    render_test(&mut vger, &device, &queue, png_name, true);
+    assert!(png_not_black(png_name));
+}
+
+#[test]
+fn test_scissor_text() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let paint = vger.linear_gradient([0.0, 0.0], [512.0, 512.0], Color::CYAN, Color::MAGENTA, 0.0);
+
+    let lorem = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna aliqua. Ut enim ad minim veniam, quis nostrud exercitation ullamco laboris nisi ut aliquip ex ea commodo consequat. Duis aute irure dolor in reprehenderit in voluptate velit esse cillum dolore eu fugiat nulla pariatur. Excepteur sint occaecat cupidatat non proident, sunt in culpa qui officia deserunt mollit anim id est laborum.";
+
+    vger.translate([32.0, 256.0]);
+    vger.scissor(euclid::rect(-100.0, -100.0, 400.0, 400.0));
+
+    let bounds = vger.text_bounds(
+        lorem,
+        FontId::DEFAULT,
+        18.0,
+        TextLayoutOptions::wrapped(448.0),
+    );
+
+    vger.stroke_rect(bounds.origin, bounds.max(), 10.0, 4.0, paint);
+
+    vger.text(
+        lorem,
+        FontId::DEFAULT,
+        18.0,
+        Color::WHITE,
+        TextLayoutOptions::wrapped(448.0),
+    );
+
+    let png_name = "text_box_scissor.png";
+    render_test(&mut vger, &device, &queue, png_name, true);
+    assert!(png_not_black(png_name));
+}
+
+#[test]
+fn segment_stroke_stress() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let paint = vger.linear_gradient([0.0, 0.0], [512.0, 512.0], Color::CYAN, Color::MAGENTA, 0.0);
+
+    for _ in 0..100000 {
+        let mut rng = rand::thread_rng();
+        let a = rand2(&mut rng);
+        let b = rand2(&mut rng);
+
+        vger.stroke_segment(a, b, 4.0, paint);
+    }
+
+    render_test(
+        &mut vger,
+        &device,
+        &queue,
+        "segment_stroke_stress.png",
+        false,
+    );
+}
+
+#[test]
+fn segment_stroke_vertical() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let paint = vger.linear_gradient(
+        [100.0, 100.0],
+        [100.0, 200.0],
+        Color::CYAN,
+        Color::MAGENTA,
+        0.0,
+    );
+
+    vger.stroke_segment([100.0, 100.0], [100.0, 200.0], 4.0, paint);
+
+    render_test(
+        &mut vger,
+        &device,
+        &queue,
+        "segment_stroke_vertical.png",
+        false,
+    );
+}
+
+#[test]
+fn segment_stroke_horizontal() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let paint = vger.linear_gradient(
+        [100.0, 100.0],
+        [200.0, 100.0],
+        Color::CYAN,
+        Color::MAGENTA,
+        0.0,
+    );
+
+    vger.stroke_segment([100.0, 100.0], [200.0, 100.0], 4.0, paint);
+
+    render_test(
+        &mut vger,
+        &device,
+        &queue,
+        "segment_stroke_horizontal.png",
+        false,
+    );
+}
+
+fn checker_pixels(format: ImageFormat) -> Vec<u8> {
+    let bpp = format.bytes_per_pixel() as usize;
+    let mut data = vec![];
+    for y in 0..8 {
+        for x in 0..8 {
+            let on = (x + y) % 2 == 0;
+            for c in 0..bpp {
+                data.push(match format {
+                    ImageFormat::Rgba16F => {
+                        // f16 1.0 is 0x3c00, little-endian.
+                        if on && c % 2 == 1 {
+                            0x3c
+                        } else {
+                            0
+                        }
+                    }
+                    _ => {
+                        if on || (bpp > 1 && c == bpp - 1) {
+                            255
+                        } else {
+                            0
+                        }
+                    }
+                });
+            }
+        }
+    }
+    data
+}
+
+#[test]
+fn image_formats() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    let formats = [
+        ImageFormat::R8,
+        ImageFormat::Rg8,
+        ImageFormat::Rgba8Srgb,
+        ImageFormat::Rgba8,
+        ImageFormat::Bgra8Srgb,
+        ImageFormat::Rgba16F,
+    ];
+
+    let mut images = vec![];
+    for format in formats {
+        let data = checker_pixels(format);
+        images.push(
+            vger.create_image_pixels(&data, 8, 8, format, None, SamplerOptions::default())
+                .unwrap(),
+        );
+    }
+
+    // Rows padded out to 16 pixels.
+    let packed = checker_pixels(ImageFormat::Rgba8Srgb);
+    let mut padded = vec![0_u8; 16 * 4 * 8];
+    for y in 0..8 {
+        padded[y * 64..y * 64 + 32].copy_from_slice(&packed[y * 32..(y + 1) * 32]);
+    }
+    images.push(
+        vger.create_image_pixels(
+            &padded,
+            8,
+            8,
+            ImageFormat::Rgba8Srgb,
+            Some(64),
+            SamplerOptions::default(),
+        )
+        .unwrap(),
+    );
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    for (i, image) in images.iter().enumerate() {
+        let origin = [20.0 + 70.0 * i as f32, 200.0];
+        let paint = vger.image_pattern(origin, [64.0, 64.0], 0.0, *image, 1.0);
+        vger.fill_rect(euclid::rect(origin[0], origin[1], 64.0, 64.0), 0.0, paint);
+    }
+
+    let png_name = "image_formats.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+}
+
+#[test]
+fn image_validation() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    let data = vec![0_u8; 16];
+
+    assert_eq!(
+        vger.create_image_pixels(
+            &data,
+            4,
+            4,
+            ImageFormat::Rgba8Srgb,
+            None,
+            SamplerOptions::default()
+        )
+        .err(),
+        Some(ImageError::DataTooShort {
+            len: 16,
+            expected: 64
+        })
+    );
+
+    assert!(matches!(
+        vger.create_image_pixels(
+            &data,
+            0,
+            0,
+            ImageFormat::R8,
+            None,
+            SamplerOptions::default()
+        ),
+        Err(ImageError::InvalidSize { .. })
+    ));
+
+    assert!(matches!(
+        vger.create_image_pixels(
+            &data,
+            4,
+            4,
+            ImageFormat::R8,
+            Some(3),
+            SamplerOptions::default()
+        ),
+        Err(ImageError::InvalidStride { .. })
+    ));
+
+    assert!(vger
+        .create_image_pixels(
+            &data,
+            4,
+            4,
+            ImageFormat::R8,
+            None,
+            SamplerOptions::default()
+        )
+        .is_ok());
+}
+
+/// A 2x2 image with red, green, blue and white texels, in reading order.
+fn quadrant_pixels() -> Vec<u8> {
+    vec![
+        255, 0, 0, 255, 0, 255, 0, 255, //
+        0, 0, 255, 255, 255, 255, 255, 255,
+    ]
+}
+
+#[test]
+fn image_pixels_sampled() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    let image = vger
+        .create_image_pixels(
+            &quadrant_pixels(),
+            2,
+            2,
+            ImageFormat::Rgba8Srgb,
+            None,
+            SamplerOptions {
+                filter: ImageFilter::Nearest,
+                ..SamplerOptions::default()
+            },
+        )
+        .unwrap();
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let paint = vger.image_pattern([0.0, 0.0], [512.0, 512.0], 0.0, image, 1.0);
+    vger.fill_rect(euclid::rect(0.0, 0.0, 512.0, 512.0), 0.0, paint);
+
+    let png_name = "image_pixels_sampled.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+
+    // Local space is y-up, so the first row of the image is at the top.
+    let pixels = read_png(png_name);
+    let pixel = |x: usize, y: usize| {
+        let i = ((512 - y) * 512 + x) * 4;
+        [pixels[i], pixels[i + 1], pixels[i + 2], pixels[i + 3]]
+    };
+    assert_eq!(pixel(128, 384), [255, 0, 0, 255]);
+    assert_eq!(pixel(384, 384), [0, 255, 0, 255]);
+    assert_eq!(pixel(128, 128), [0, 0, 255, 255]);
+    assert_eq!(pixel(384, 128), [255, 255, 255, 255]);
+}
+
+#[test]
+fn image_pattern_alpha() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    let image = vger
+        .create_image_pixels(
+            &quadrant_pixels(),
+            2,
+            2,
+            ImageFormat::Rgba8Srgb,
+            None,
+            SamplerOptions {
+                filter: ImageFilter::Nearest,
+                ..SamplerOptions::default()
+            },
+        )
+        .unwrap();
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    // Left half opaque, right half faded.
+    let opaque = vger.image_pattern([0.0, 0.0], [512.0, 512.0], 0.0, image, 1.0);
+    vger.fill_rect(euclid::rect(0.0, 0.0, 256.0, 512.0), 0.0, opaque);
+    let faded = vger.image_pattern([0.0, 0.0], [512.0, 512.0], 0.0, image, 0.5);
+    vger.fill_rect(euclid::rect(256.0, 0.0, 256.0, 512.0), 0.0, faded);
+
+    let png_name = "image_pattern_alpha.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+
+    let pixels = read_png(png_name);
+    let pixel = |x: usize, y: usize| {
+        let i = ((512 - y) * 512 + x) * 4;
+        [pixels[i], pixels[i + 1], pixels[i + 2]]
+    };
+
+    // Faded texels are blended halfway to the black background, in
+    // linear space.
+    let half = 186..=189;
+    assert_eq!(pixel(128, 128), [0, 0, 255]);
+    let white = pixel(384, 128);
+    assert!(white.iter().all(|c| half.contains(c)), "{:?}", white);
+    let green = pixel(384, 384);
+    assert!(green[0] == 0 && half.contains(&green[1]) && green[2] == 0);
+}
+
+#[test]
+fn image_update() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    let data = checker_pixels(ImageFormat::Rgba8Srgb);
+    let image = vger
+        .create_image_pixels(
+            &data,
+            8,
+            8,
+            ImageFormat::Rgba8Srgb,
+            None,
+            SamplerOptions::default(),
+        )
+        .unwrap();
+
+    // Paint a cyan 4x4 block into the middle, from a source with 5 pixel rows.
+    let mut block = vec![];
+    for _ in 0..4 {
+        block.extend_from_slice(&[0, 255, 255, 255].repeat(4));
+        block.extend_from_slice(&[0, 0, 0, 0]);
+    }
+    vger.update_image(image, euclid::rect(2, 2, 4, 4), &block, Some(20))
+        .unwrap();
+
+    assert_eq!(
+        vger.update_image(image, euclid::rect(6, 6, 4, 4), &block, Some(20)),
+        Err(ImageError::OutOfBounds {
+            x: 6,
+            y: 6,
+            width: 4,
+            height: 4
+        })
+    );
+
+    assert!(matches!(
+        vger.update_image(image, euclid::rect(0, 0, 4, 4), &block[..30], None),
+        Err(ImageError::DataTooShort { .. })
+    ));
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let paint = vger.image_pattern([100.0, 100.0], [256.0, 256.0], 0.0, image, 1.0);
+    vger.fill_rect(euclid::rect(100.0, 100.0, 256.0, 256.0), 0.0, paint);
+
+    let png_name = "image_update.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    vger.resize_image(image, 16, 16).unwrap();
+    vger.update_image(image, euclid::rect(12, 12, 4, 4), &block, Some(20))
+        .unwrap();
+
+    vger.delete_image(image);
+    assert_eq!(
+        vger.update_image(image, euclid::rect(0, 0, 4, 4), &block, Some(20)),
+        Err(ImageError::InvalidImage)
+    );
+    assert_eq!(
+        vger.resize_image(image, 4, 4),
+        Err(ImageError::InvalidImage)
+    );
+}
+
+#[test]
+fn draw_image() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    // 16x8 image: left half checkered, right half cyan.
+    let mut data = vec![];
+    for y in 0..8 {
+        for x in 0..16 {
+            if x >= 8 {
+                data.extend_from_slice(&[0, 255, 255, 255]);
+            } else if (x + y) % 2 == 0 {
+                data.extend_from_slice(&[255, 255, 255, 255]);
+            } else {
+                data.extend_from_slice(&[0, 0, 0, 255]);
+            }
+        }
+    }
+    let image = vger
+        .create_image_pixels(
+            &data,
+            16,
+            8,
+            ImageFormat::Rgba8Srgb,
+            None,
+            SamplerOptions::default(),
+        )
+        .unwrap();
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let fits = [
+        ImageFit::Fill,
+        ImageFit::Contain,
+        ImageFit::Cover,
+        ImageFit::None,
+    ];
+
+    for (i, fit) in fits.iter().enumerate() {
+        let x = 10.0 + 125.0 * i as f32;
+        let frame = vger.color_paint(Color::gray(0.3));
+        vger.stroke_rect(
+            [x, 300.0].into(),
+            [x + 100.0, 400.0].into(),
+            0.0,
+            1.0,
+            frame,
+        );
+        vger.draw_image(
+            image,
+            None,
+            euclid::rect(x, 300.0, 100.0, 100.0),
+            10.0,
+            *fit,
+            1.0,
+        )
+        .unwrap();
+
+        // Only the checkered half.
+        vger.draw_image(
+            image,
+            Some(euclid::rect(0, 0, 8, 8)),
+            euclid::rect(x, 100.0, 100.0, 100.0),
+            0.0,
+            *fit,
+            0.5,
+        )
+        .unwrap();
+    }
+
+    let png_name = "draw_image.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    vger.delete_image(image);
+    assert_eq!(
+        vger.draw_image(
+            image,
+            None,
+            euclid::rect(0.0, 0.0, 1.0, 1.0),
+            0.0,
+            ImageFit::Fill,
+            1.0
+        ),
+        Err(ImageError::InvalidImage)
+    );
+}
+
+#[test]
+fn draw_image_nine_slice() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    // 24x24 panel: white 8 pixel border with magenta corners, checkered center.
+    let mut data = vec![];
+    for y in 0..24 {
+        for x in 0..24 {
+            let edge_x = !(8..16).contains(&x);
+            let edge_y = !(8..16).contains(&y);
+            let pixel = if edge_x && edge_y {
+                [255, 0, 255, 255]
+            } else if edge_x || edge_y {
+                [255, 255, 255, 255]
+            } else if (x / 2 + y / 2) % 2 == 0 {
+                [0, 255, 255, 255]
+            } else {
+                [0, 0, 0, 255]
+            };
+            data.extend_from_slice(&pixel);
+        }
+    }
+    let image = vger
+        .create_image_pixels(
+            &data,
+            24,
+            24,
+            ImageFormat::Rgba8Srgb,
+            None,
+            SamplerOptions::default(),
+        )
+        .unwrap();
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let insets = ImageInsets::new_all_same(8);
+    vger.draw_image_nine_slice(
+        image,
+        insets,
+        euclid::rect(20.0, 20.0, 200.0, 100.0),
+        SliceMode::Stretch,
+    )
+    .unwrap();
+    vger.draw_image_nine_slice(
+        image,
+        insets,
+        euclid::rect(20.0, 200.0, 300.0, 60.0),
+        SliceMode::Tile,
+    )
+    .unwrap();
+
+    // Smaller than the corners.
+    vger.draw_image_nine_slice(
+        image,
+        insets,
+        euclid::rect(400.0, 400.0, 10.0, 10.0),
+        SliceMode::Stretch,
+    )
+    .unwrap();
+
+    assert_eq!(
+        vger.draw_image_nine_slice(
+            image,
+            ImageInsets::new(8, 10, 8, 20),
+            euclid::rect(0.0, 0.0, 10.0, 10.0),
+            SliceMode::Stretch
+        ),
+        Err(ImageError::InvalidInsets)
+    );
+
+    let png_name = "draw_image_nine_slice.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // Pixel (x, y) covers local y from y - 1 to y.
+    let pixels = read_png(png_name);
+    let pixel = |x: usize, y: usize| {
+        let i = ((512 - y) * 512 + x) * 4;
+        [pixels[i], pixels[i + 1], pixels[i + 2]]
+    };
+    let (magenta, white, cyan, black) = ([255, 0, 255], [255, 255, 255], [0, 255, 255], [0, 0, 0]);
+
+    // Stretched: the corners stay 8 pixels square, and the center's
+    // 2 pixel checks stretch to 46 by 21. Scaled up corners would reach
+    // past x = 60. Linear filtering blends texels across the stretched
+    // slices' edges, so samples there keep clear of them.
+    for (x, y) in [(21, 22), (27, 28), (212, 119), (218, 113)] {
+        assert_eq!(pixel(x, y), magenta, "{} {}", x, y);
+    }
+    for (x, y) in [(60, 24), (120, 24), (24, 60), (24, 80), (180, 116)] {
+        assert_eq!(pixel(x, y), white, "{} {}", x, y);
+    }
+    for (x, y) in [(51, 102), (97, 81), (143, 102)] {
+        assert_eq!(pixel(x, y), cyan, "{} {}", x, y);
+    }
+    for (x, y) in [(97, 102), (51, 81)] {
+        assert_eq!(pixel(x, y), black, "{} {}", x, y);
+    }
+
+    // Tiled: the center repeats its 8 pixels at their size, starting
+    // from the top left.
+    for x in (28..312).step_by(8) {
+        assert_eq!(pixel(x, 252), cyan, "{}", x);
+        assert_eq!(pixel(x + 2, 252), black, "{}", x);
+        assert_eq!(pixel(x, 250), black, "{}", x);
+    }
+    assert_eq!(pixel(24, 230), white);
+    assert_eq!(pixel(24, 204), magenta);
+}
+
+#[test]
+fn image_sampler_options() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    // 4x4 checkerboard of red and blue.
+    let mut data = vec![];
+    for y in 0..4 {
+        for x in 0..4 {
+            if (x + y) % 2 == 0 {
+                data.extend_from_slice(&[255, 0, 0, 255]);
+            } else {
+                data.extend_from_slice(&[0, 0, 255, 255]);
+            }
+        }
+    }
+
+    let options = [
+        SamplerOptions::default(),
+        SamplerOptions {
+            filter: ImageFilter::Nearest,
+            ..Default::default()
+        },
+        SamplerOptions {
+            filter: ImageFilter::Nearest,
+            wrap: ImageWrap::Repeat,
+            mipmaps: false,
+        },
+        SamplerOptions {
+            wrap: ImageWrap::Mirror,
+            mipmaps: true,
+            ..Default::default()
+        },
+    ];
+
+    let images: Vec<_> = options
+        .iter()
+        .map(|options| {
+            vger.create_image_pixels(&data, 4, 4, ImageFormat::Rgba8Srgb, None, *options)
+                .unwrap()
+        })
+        .collect();
+
+    // Updating a mipmapped image regenerates its mip chain.
+    vger.update_image(images[3], euclid::rect(0, 0, 2, 2), &[255; 16], None)
+        .unwrap();
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    for (i, image) in images.iter().enumerate() {
+        let x = 10.0 + 125.0 * i as f32;
+
+        // Magnified, two tiles across.
+        let paint = vger.image_pattern([x, 300.0], [50.0, 50.0], 0.0, *image, 1.0);
+        vger.fill_rect(euclid::rect(x, 300.0, 100.0, 100.0), 0.0, paint);
+
+        // Minified, four texels per pixel.
+        let paint = vger.image_pattern([x, 100.0], [1.0, 1.0], 0.0, *image, 1.0);
+        vger.fill_rect(euclid::rect(x, 100.0, 100.0, 100.0), 0.0, paint);
+    }
+
+    let png_name = "image_sampler_options.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+}
+
+#[test]
+fn image_update_mipmaps() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    let red = [255, 0, 0, 255].repeat(64 * 64);
+    let image = vger
+        .create_image_pixels(
+            &red,
+            64,
+            64,
+            ImageFormat::Rgba8Srgb,
+            None,
+            SamplerOptions {
+                mipmaps: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+    // Only the mip levels under the top left quadrant are redrawn.
+    let blue = [0, 0, 255, 255].repeat(32 * 32);
+    vger.update_image(image, euclid::rect(0, 0, 32, 32), &blue, None)
+        .unwrap();
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    // Minified four times, so mip level 2 is sampled.
+    let paint = vger.image_pattern([0.0, 0.0], [16.0, 16.0], 0.0, image, 1.0);
+    vger.fill_rect(euclid::rect(0.0, 0.0, 16.0, 16.0), 0.0, paint);
+
+    let png_name = "image_update_mipmaps.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+
+    let pixels = read_png(png_name);
+    let pixel = |x: usize, y: usize| {
+        let i = ((512 - y) * 512 + x) * 4;
+        [pixels[i], pixels[i + 1], pixels[i + 2]]
+    };
+    // Away from the rect's diagonal, where the mip level picked jumps.
+    assert_eq!(pixel(2, 10), [0, 0, 255]);
+    assert_eq!(pixel(12, 12), [255, 0, 0]);
+    assert_eq!(pixel(4, 4), [255, 0, 0]);
+    assert_eq!(pixel(13, 6), [255, 0, 0]);
+}
+
+fn draw_icons(vger: &mut Vger) {
+    // 60 small icons: a colored disc on a checkered background.
+    let mut icons = vec![];
+    for i in 0..60 {
+        let mut data = vec![];
+        for y in 0..16 {
+            for x in 0..16 {
+                let (dx, dy) = (x as f32 - 7.5, y as f32 - 7.5);
+                let pixel = if dx * dx + dy * dy < 36.0 {
+                    [(i * 4) as u8, 255 - (i * 4) as u8, 128, 255]
+                } else if (x / 4 + y / 4) % 2 == 0 {
+                    [255, 255, 255, 255]
+                } else {
+                    [64, 64, 64, 255]
+                };
+                data.extend_from_slice(&pixel);
+            }
+        }
+        icons.push(
+            vger.create_image_pixels(
+                &data,
+                16,
+                16,
+                ImageFormat::Rgba8Srgb,
+                None,
+                SamplerOptions::default(),
+            )
+            .unwrap(),
+        );
+    }
+
+    // Updates go to the image's region of the atlas.
+    vger.update_image(icons[0], euclid::rect(4, 4, 8, 8), &[255; 8 * 8 * 4], None)
+        .unwrap();
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    for (i, icon) in icons.iter().enumerate() {
+        let x = 10.0 + 50.0 * (i % 10) as f32;
+        let y = 10.0 + 50.0 * (i / 10) as f32;
+        vger.draw_image(
+            *icon,
+            None,
+            euclid::rect(x, y, 40.0, 40.0),
+            8.0,
+            ImageFit::Fill,
+            1.0,
+        )
+        .unwrap();
+    }
+
+    vger.draw_image_nine_slice(
+        icons[1],
+        ImageInsets::new_all_same(4),
+        euclid::rect(10.0, 320.0, 300.0, 60.0),
+        SliceMode::Tile,
+    )
+    .unwrap();
+
+    // Repeat at the edges, clamped rather than bleeding into neighbors.
+    let paint = vger.image_pattern([350.0, 350.0], [100.0, 100.0], 0.3, icons[2], 1.0);
+    vger.fill_rect(euclid::rect(320.0, 320.0, 180.0, 180.0), 0.0, paint);
+}
+
+#[test]
+fn image_atlas() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+    draw_icons(&mut vger);
+    render_test(&mut vger, &device, &queue, "image_no_atlas.png", false);
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+    vger.set_image_atlas(true);
+    draw_icons(&mut vger);
+
+    let png_name = "image_atlas.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // Atlased images look the same as standalone ones.
+    let expected = read_png("image_no_atlas.png");
+    let actual = read_png(png_name);
+    let max_diff = expected
+        .iter()
+        .zip(actual.iter())
+        .map(|(a, b)| a.abs_diff(*b))
+        .max()
+        .unwrap();
+    assert!(max_diff <= 2, "max difference {}", max_diff);
+}
+
+#[test]
+fn image_atlas_reuse() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+    vger.set_image_atlas(true);
+
+    let solid = |color: [u8; 4]| color.repeat(128 * 128);
+    let create = |vger: &mut Vger, color: [u8; 4]| {
+        vger.create_image_pixels(
+            &solid(color),
+            128,
+            128,
+            ImageFormat::Rgba8Srgb,
+            None,
+            SamplerOptions::default(),
+        )
+        .unwrap()
+    };
+
+    // Fill the atlas, then overflow it into a texture of its own.
+    let mut reds: Vec<_> = (0..49)
+        .map(|_| create(&mut vger, [255, 0, 0, 255]))
+        .collect();
+    let green = create(&mut vger, [0, 255, 0, 255]);
+
+    // The deleted image's space is reused from the next frame.
+    vger.delete_image(reds.pop().unwrap());
+    vger.begin(512.0, 512.0, 1.0);
+    let blue = create(&mut vger, [0, 0, 255, 255]);
+
+    for (i, image) in [reds[47], green, blue].iter().enumerate() {
+        let x = 10.0 + 150.0 * i as f32;
+        vger.draw_image(
+            *image,
+            None,
+            euclid::rect(x, 10.0, 128.0, 128.0),
+            0.0,
+            ImageFit::Fill,
+            1.0,
+        )
+        .unwrap();
+    }
+
+    let png_name = "image_atlas_reuse.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+
+    let pixels = read_png(png_name);
+    let pixel = |x: usize, y: usize| {
+        let i = ((512 - y) * 512 + x) * 4;
+        [pixels[i], pixels[i + 1], pixels[i + 2]]
+    };
+    assert_eq!(pixel(74, 74), [255, 0, 0]);
+    assert_eq!(pixel(224, 74), [0, 255, 0]);
+    assert_eq!(pixel(374, 74), [0, 0, 255]);
+}
+
+#[test]
+fn register_texture() {
+    let (device, queue) = setup();
+
+    // Render a "preview" into a texture we own.
+    let preview = device.create_texture(&wgpu::TextureDescriptor {
+        size: wgpu::Extent3d {
+            width: 128,
+            height: 128,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        label: Some("preview"),
+        view_formats: &[],
+    });
+    let preview_view = preview.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let mut preview_vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+    preview_vger.begin(128.0, 128.0, 1.0);
+    let paint =
+        preview_vger.linear_gradient([0.0, 0.0], [128.0, 128.0], Color::CYAN, Color::MAGENTA, 0.0);
+    preview_vger.fill_rect(euclid::rect(0.0, 0.0, 128.0, 128.0), 0.0, paint);
+    let paint = preview_vger.color_paint(Color::WHITE);
+    preview_vger.fill_circle([64.0, 64.0], 32.0, paint);
+    preview_vger.encode(&wgpu::RenderPassDescriptor {
+        label: None,
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: &preview_view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        occlusion_query_set: None,
+        timestamp_writes: None,
+    });
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    let image = vger.register_texture(&preview_view, 128, 128, SamplerOptions::default());
+
+    assert_eq!(
+        vger.update_image(image, euclid::rect(0, 0, 1, 1), &[0; 4], None),
+        Err(ImageError::ExternalTexture)
+    );
+    assert_eq!(
+        vger.resize_image(image, 64, 64),
+        Err(ImageError::ExternalTexture)
+    );
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    vger.draw_image(
+        image,
+        None,
+        euclid::rect(20.0, 20.0, 200.0, 200.0),
+        30.0,
+        ImageFit::Fill,
+        1.0,
+    )
+    .unwrap();
+
+    vger.save();
+    vger.scissor(euclid::rect(300.0, 300.0, 100.0, 100.0));
+    vger.draw_image(
+        image,
+        None,
+        euclid::rect(250.0, 250.0, 200.0, 200.0),
+        0.0,
+        ImageFit::Fill,
+        1.0,
+    )
+    .unwrap();
+    vger.restore();
+
+    let png_name = "register_texture.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    vger.unregister_texture(image);
+    assert_eq!(
+        vger.draw_image(
+            image,
+            None,
+            euclid::rect(0.0, 0.0, 1.0, 1.0),
+            0.0,
+            ImageFit::Fill,
+            1.0
+        ),
+        Err(ImageError::InvalidImage)
+    );
+}
+
+#[test]
+fn image_handle_reuse() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    let white = [255; 4 * 4 * 4];
+    let create = |vger: &mut Vger| {
+        vger.create_image_pixels(
+            &white,
+            4,
+            4,
+            ImageFormat::Rgba8Srgb,
+            None,
+            SamplerOptions::default(),
+        )
+        .unwrap()
+    };
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    // Paint created before the image is deleted.
+    let a = create(&mut vger);
+    let paint = vger.image_pattern([200.0, 200.0], [100.0, 100.0], 0.0, a, 1.0);
+    vger.fill_rect(euclid::rect(200.0, 200.0, 100.0, 100.0), 0.0, paint);
+
+    vger.delete_image(a);
+    vger.delete_image(a);
+
+    // Reuses a's slot, but a stays invalid.
+    let b = create(&mut vger);
+    assert_ne!(a, b);
+    assert_eq!(
+        vger.update_image(a, euclid::rect(0, 0, 1, 1), &[0; 4], None),
+        Err(ImageError::InvalidImage)
+    );
+    vger.update_image(b, euclid::rect(0, 0, 1, 1), &[0; 4], None)
+        .unwrap();
+
+    // Drawing with the stale handle draws nothing.
+    let paint = vger.image_pattern([200.0, 10.0], [100.0, 100.0], 0.0, a, 1.0);
+    vger.fill_rect(euclid::rect(200.0, 10.0, 100.0, 100.0), 0.0, paint);
+
+    vger.draw_image(
+        b,
+        None,
+        euclid::rect(10.0, 10.0, 100.0, 100.0),
+        0.0,
+        ImageFit::Fill,
+        1.0,
+    )
+    .unwrap();
+
+    let png_name = "image_handle_reuse.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // Nothing drawn in the middle of the rect painted with a.
+    let pixels = read_png(png_name);
+    let i = (250 * 512 + 250) * 4;
+    assert_eq!(&pixels[i..i + 3], &[0, 0, 0]);
+}
+
+#[cfg(feature = "image-decode")]
+fn encode_png(
+    width: u32,
+    height: u32,
+    color: png::ColorType,
+    depth: png::BitDepth,
+    palette: Option<(&[u8], &[u8])>,
+    data: &[u8],
+) -> Vec<u8> {
+    let mut bytes = vec![];
+    {
+        let mut encoder = png::Encoder::new(&mut bytes, width, height);
+        encoder.set_color(color);
+        encoder.set_depth(depth);
+        if let Some((palette, trns)) = palette {
+            encoder.set_palette(palette);
+            encoder.set_trns(trns);
+        }
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(data).unwrap();
+    }
+    bytes
+}
+
+#[cfg(feature = "image-decode")]
+#[test]
+fn image_decode() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    let gradient: Vec<u8> = (0..64).map(|i| i * 4).collect();
+    let gray = encode_png(
+        8,
+        8,
+        png::ColorType::Grayscale,
+        png::BitDepth::Eight,
+        None,
+        &gradient,
+    );
+
+    // Two color palette, one bit per pixel, with a transparent entry.
+    let palette = encode_png(
+        8,
+        8,
+        png::ColorType::Indexed,
+        png::BitDepth::One,
+        Some((&[255, 128, 0, 0, 0, 0], &[255, 0])),
+        &[0b1010_1010, 0b0101_0101].repeat(4),
+    );
+
+    // 16 bit RGBA.
+    let rgba16: Vec<u8> = (0..64)
+        .flat_map(|i| [0xff, 0xff, 0, 0, (i * 4) as u8, 0, 0xff, 0xff])
+        .collect();
+    let rgba16 = encode_png(
+        8,
+        8,
+        png::ColorType::Rgba,
+        png::BitDepth::Sixteen,
+        None,
+        &rgba16,
+    );
+
+    let images = [
+        vger.create_image_from_bytes(include_bytes!("quadrants.jpg"))
+            .unwrap(),
+        vger.create_image_from_bytes(include_bytes!("gray.jpg"))
+            .unwrap(),
+        vger.create_image_from_bytes(&gray).unwrap(),
+        vger.create_image_from_bytes(&palette).unwrap(),
+        vger.create_image_from_bytes(&rgba16).unwrap(),
+    ];
+
+    assert_eq!(
+        vger.create_image_from_bytes(b"not an image"),
+        Err(ImageError::UnknownFormat)
+    );
+    assert!(matches!(
+        vger.create_image_from_bytes(&gray[..gray.len() / 2]),
+        Err(ImageError::Decode(_))
+    ));
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    for (i, image) in images.iter().enumerate() {
+        vger.draw_image(
+            *image,
+            None,
+            euclid::rect(10.0 + 100.0 * i as f32, 10.0, 80.0, 80.0),
+            0.0,
+            ImageFit::Contain,
+            1.0,
+        )
+        .unwrap();
+    }
+
+    let png_name = "image_decode.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // The JPEG's top left quadrant is red. It's drawn 80x40 at (10, 30),
+    // and rows of the PNG go down from the top.
+    let pixels = read_png(png_name);
+    let i = ((512 - 60) * 512 + 30) * 4;
+    let (r, g, b) = (pixels[i], pixels[i + 1], pixels[i + 2]);
+    assert!(r > 200 && g < 60 && b < 60, "{} {} {}", r, g, b);
+}
+
+#[cfg(feature = "image-decode")]
+#[test]
+fn image_decode_alpha() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    // Half transparent red, stored with straight alpha.
+    let red = encode_png(
+        2,
+        2,
+        png::ColorType::Rgba,
+        png::BitDepth::Eight,
+        None,
+        &[255, 0, 0, 128].repeat(4),
+    );
+    let image = vger.create_image_from_bytes(&red).unwrap();
+
+    vger.begin(512.0, 512.0, 1.0);
+    vger.draw_image(
+        image,
+        None,
+        euclid::rect(0.0, 0.0, 512.0, 512.0),
+        0.0,
+        ImageFit::Fill,
+        1.0,
+    )
+    .unwrap();
+
+    let png_name = "image_decode_alpha.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+
+    // Blended halfway to the black background in linear space. Colors
+    // premultiplied before blending would come out far darker.
+    let pixels = read_png(png_name);
+    let i = (256 * 512 + 256) * 4;
+    let (r, g, b) = (pixels[i], pixels[i + 1], pixels[i + 2]);
+    assert!(
+        (186..=189).contains(&r) && g == 0 && b == 0,
+        "{} {} {}",
+        r,
+        g,
+        b
+    );
+}
+
+#[test]
+fn mask_image() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    // 32x32 ring icon.
+    let mut data = vec![];
+    for y in 0..32 {
+        for x in 0..32 {
+            let (dx, dy) = (x as f32 - 15.5, y as f32 - 15.5);
+            let r = (dx * dx + dy * dy).sqrt();
+            data.push(if (8.0..14.0).contains(&r) { 255 } else { 0 });
+        }
+    }
+    let mask = vger
+        .create_image_pixels(
+            &data,
+            32,
+            32,
+            ImageFormat::R8,
+            None,
+            SamplerOptions::default(),
+        )
+        .unwrap();
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let paints = [
+        vger.color_paint(Color::CYAN),
+        vger.linear_gradient([0.0, 0.0], [512.0, 0.0], Color::MAGENTA, Color::CYAN, 0.0),
+        vger.checkerboard(8.0, 0.0, Color::WHITE, Color::new(1.0, 0.5, 0.0, 1.0)),
+    ];
+
+    for (i, paint) in paints.iter().enumerate() {
+        let x = 10.0 + 170.0 * i as f32;
+        vger.draw_mask(mask, euclid::rect(x, 300.0, 160.0, 160.0), *paint)
+            .unwrap();
+    }
+
+    // Mask a circle, rather than a rect.
+    let paint = vger
+        .mask_paint(paints[0], mask, [100.0, 50.0], [200.0, 200.0], 0.0)
+        .unwrap();
+    vger.fill_circle([200.0, 150.0], 80.0, paint);
+
+    // Image paints, including masked ones, can't be masked, since the
+    // mask would replace their image.
+    let image_paint = vger.image_pattern([0.0, 0.0], [32.0, 32.0], 0.0, mask, 1.0);
+    for unmaskable in [image_paint, paint] {
+        assert_eq!(
+            vger.mask_paint(unmaskable, mask, [0.0, 0.0], [32.0, 32.0], 0.0)
+                .err(),
+            Some(ImageError::MaskedImagePaint)
+        );
+    }
+    assert_eq!(
+        vger.draw_mask(mask, euclid::rect(0.0, 0.0, 32.0, 32.0), image_paint),
+        Err(ImageError::MaskedImagePaint)
+    );
+
+    let png_name = "mask_image.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // The first ring is cyan, with nothing drawn in its center. Its
+    // center is at (90, 380), rows go down from the top.
+    let pixels = read_png(png_name);
+    let pixel = |x: usize, y: usize| {
+        let i = ((512 - y) * 512 + x) * 4;
+        [pixels[i], pixels[i + 1], pixels[i + 2]]
+    };
+    assert_eq!(pixel(90, 380), [0, 0, 0]);
+    assert_eq!(pixel(90 + 55, 380), [0, 255, 255]);
+
+    vger.delete_image(mask);
+    assert_eq!(
+        vger.draw_mask(mask, euclid::rect(0.0, 0.0, 1.0, 1.0), paints[0]),
+        Err(ImageError::InvalidImage)
+    );
+}
+
+#[test]
+fn image_adjustments() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    // Red, green, blue and white stripes.
+    let mut data = vec![];
+    for _ in 0..4 {
+        for color in [
+            [255, 0, 0, 255],
+            [0, 255, 0, 255],
+            [0, 0, 255, 255],
+            [255; 4],
+        ] {
+            data.extend_from_slice(&color);
+        }
+    }
+    let image = vger
+        .create_image_pixels(
+            &data,
+            4,
+            4,
+            ImageFormat::Rgba8Srgb,
+            None,
+            SamplerOptions {
+                filter: ImageFilter::Nearest,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let adjustments = [
+        ImageAdjustments::default(),
+        ImageAdjustments {
+            color_matrix: Some(ColorMatrix::grayscale()),
+            ..Default::default()
+        },
+        ImageAdjustments {
+            tint: Color::new(1.0, 0.5, 0.0, 1.0),
+            ..Default::default()
+        },
+        ImageAdjustments {
+            opacity: 0.5,
+            color_matrix: Some(ColorMatrix::brightness(0.5)),
+            ..Default::default()
+        },
+    ];
+
+    for (i, adjustments) in adjustments.iter().enumerate() {
+        let x = 10.0 + 125.0 * i as f32;
+        let paint = vger.image_pattern([x, 200.0], [100.0, 100.0], 0.0, image, 1.0);
+        let paint = vger.adjust_image_paint(paint, adjustments).unwrap();
+        vger.fill_rect(euclid::rect(x, 200.0, 100.0, 100.0), 0.0, paint);
+    }
+
+    // Repeated adjustments share a color matrix.
+    for y in [320.0, 430.0] {
+        let paint = vger.image_pattern([10.0, y], [100.0, 60.0], 0.0, image, 1.0);
+        let paint = vger.adjust_image_paint(paint, &adjustments[1]).unwrap();
+        vger.fill_rect(euclid::rect(10.0, y, 100.0, 60.0), 0.0, paint);
+    }
+
+    let png_name = "image_adjustments.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    let pixels = read_png(png_name);
+    let pixel = |x: usize, y: usize| {
+        let i = ((512 - y) * 512 + x) * 4;
+        [pixels[i], pixels[i + 1], pixels[i + 2]]
+    };
+    let to_srgb = |x: f32| {
+        let s = if x <= 0.0031308 {
+            12.92 * x
+        } else {
+            1.055 * x.powf(1.0 / 2.4) - 0.055
+        };
+        (s * 255.0).round() as i32
+    };
+    let close = |actual: [u8; 3], expected: [i32; 3]| {
+        actual
+            .iter()
+            .zip(expected.iter())
+            .all(|(a, e)| (*a as i32 - e).abs() <= 2)
+    };
+
+    // Red stripe, in each adjusted copy.
+    assert_eq!(pixel(22, 250), [255, 0, 0]);
+    let gray = to_srgb(0.213);
+    assert!(close(pixel(147, 250), [gray, gray, gray]));
+    assert!(close(pixel(272, 250), [255, 0, 0]));
+
+    // Green stripe tinted orange.
+    assert!(close(pixel(297, 250), [0, to_srgb(0.5), 0]));
+
+    // Half brightness, half opacity over black.
+    let half = to_srgb(0.25);
+    assert!(close(pixel(397, 250), [half, 0, 0]));
+
+    assert!(close(pixel(22, 350), [gray, gray, gray]));
+    assert!(close(pixel(22, 460), [gray, gray, gray]));
+
+    // Paint indices past the end of this frame's paints are caught.
+    let stale = vger.image_pattern([0.0, 0.0], [1.0, 1.0], 0.0, image, 1.0);
+    vger.begin(512.0, 512.0, 1.0);
+    assert_eq!(
+        vger.adjust_image_paint(stale, &adjustments[1]).err(),
+        Some(ImageError::InvalidPaint)
+    );
+}
+
+/// Converts 8-bit YUV to 8-bit sRGB with the published BT.601 and
+/// BT.709 equations.
+fn yuv_reference(y: u8, u: u8, v: u8, color_space: YuvColorSpace, range: YuvRange) -> [i32; 3] {
+    let (y, cb, cr) = match range {
+        YuvRange::Limited => (
+            (y as f32 - 16.0) / 219.0,
+            (u as f32 - 128.0) / 224.0,
+            (v as f32 - 128.0) / 224.0,
+        ),
+        YuvRange::Full => (
+            y as f32 / 255.0,
+            (u as f32 - 128.0) / 255.0,
+            (v as f32 - 128.0) / 255.0,
+        ),
+    };
+    let [r, g, b] = match color_space {
+        YuvColorSpace::Bt601 => [
+            y + 1.402 * cr,
+            y - 0.344136 * cb - 0.714136 * cr,
+            y + 1.772 * cb,
+        ],
+        YuvColorSpace::Bt709 => [
+            y + 1.5748 * cr,
+            y - 0.187324 * cb - 0.468124 * cr,
+            y + 1.8556 * cb,
+        ],
+    };
+    [r, g, b].map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as i32)
+}
+
+#[test]
+fn yuv_images() {
+    let (device, queue) = setup();
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+
+    // A 16x16 frame with a luma gradient and chroma varying per 2x2
+    // block, covering most of the range.
+    const SIZE: usize = 16;
+    let luma: Vec<u8> = (0..SIZE * SIZE)
+        .map(|i| (16 + (i * 7) % 224) as u8)
+        .collect();
+    let chroma_u: Vec<u8> = (0..SIZE * SIZE / 4)
+        .map(|i| (16 + (i * 37) % 225) as u8)
+        .collect();
+    let chroma_v: Vec<u8> = (0..SIZE * SIZE / 4)
+        .map(|i| (240 - (i * 53) % 225) as u8)
+        .collect();
+    let chroma_uv: Vec<u8> = chroma_u
+        .iter()
+        .zip(chroma_v.iter())
+        .flat_map(|(u, v)| [*u, *v])
+        .collect();
+
+    let options = SamplerOptions {
+        filter: ImageFilter::Nearest,
+        ..Default::default()
+    };
+
+    let nv12 = vger
+        .create_image_yuv(
+            YuvFormat::Nv12,
+            SIZE as u32,
+            SIZE as u32,
+            YuvColorSpace::Bt709,
+            YuvRange::Limited,
+            &[YuvPlane::new(&luma), YuvPlane::new(&chroma_uv)],
+            options,
+        )
+        .unwrap();
+
+    let i420 = vger
+        .create_image_yuv(
+            YuvFormat::I420,
+            SIZE as u32,
+            SIZE as u32,
+            YuvColorSpace::Bt601,
+            YuvRange::Full,
+            &[
+                YuvPlane::new(&luma),
+                YuvPlane::new(&chroma_u),
+                YuvPlane::new(&chroma_v),
+            ],
+            options,
+        )
+        .unwrap();
+
+    // Replace the middle of the NV12 frame with a flat color.
+    let patch_luma = [200u8; 8 * 8];
+    let patch_uv = [[90u8, 170u8]; 4 * 4].concat();
+    vger.update_image_yuv(
+        nv12,
+        euclid::rect(4, 4, 8, 8),
+        &[YuvPlane::new(&patch_luma), YuvPlane::new(&patch_uv)],
+    )
+    .unwrap();
+
+    // Chroma is shared by 2x2 blocks, so updates must line up with them.
+    assert_eq!(
+        vger.update_image_yuv(
+            nv12,
+            euclid::rect(3, 4, 8, 8),
+            &[YuvPlane::new(&patch_luma), YuvPlane::new(&patch_uv)],
+        ),
+        Err(ImageError::UnalignedRect)
+    );
+    assert_eq!(
+        vger.create_image_yuv(
+            YuvFormat::I420,
+            SIZE as u32,
+            SIZE as u32,
+            YuvColorSpace::Bt601,
+            YuvRange::Full,
+            &[YuvPlane::new(&luma), YuvPlane::new(&chroma_uv)],
+            options,
+        ),
+        Err(ImageError::InvalidPlanes {
+            expected: 3,
+            found: 2
+        })
+    );
+    assert_eq!(
+        vger.update_image(nv12, euclid::rect(0, 0, 1, 1), &[0], None),
+        Err(ImageError::InvalidPlanes {
+            expected: 2,
+            found: 1
+        })
+    );
+    let rgba = vger
+        .create_image_pixels(
+            &[0; 4],
+            1,
+            1,
+            ImageFormat::Rgba8,
+            None,
+            SamplerOptions::default(),
+        )
+        .unwrap();
+    assert_eq!(
+        vger.update_image_yuv(rgba, euclid::rect(0, 0, 1, 1), &[YuvPlane::new(&[0])]),
+        Err(ImageError::NotYuv)
+    );
+
+    // Each image pixel covers an 8x8 block of the render.
+    const SCALE: f32 = 8.0;
+    let extent = SIZE as f32 * SCALE;
+    vger.begin(512.0, 512.0, 1.0);
+    for (i, image) in [nv12, i420].iter().enumerate() {
+        let x = 50.0 + 200.0 * i as f32;
+        let paint = vger.image_pattern([x, 200.0], [extent, extent], 0.0, *image, 1.0);
+        vger.fill_rect(euclid::rect(x, 200.0, extent, extent), 0.0, paint);
+    }
+
+    let png_name = "yuv_images.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    let pixels = read_png(png_name);
+    let pixel = |x: usize, y: usize| {
+        let i = ((512 - y) * 512 + x) * 4;
+        [pixels[i], pixels[i + 1], pixels[i + 2]]
+    };
+
+    for (i, &(color_space, range)) in [
+        (YuvColorSpace::Bt709, YuvRange::Limited),
+        (YuvColorSpace::Bt601, YuvRange::Full),
+    ]
+    .iter()
+    .enumerate()
+    {
+        for row in 0..SIZE {
+            for col in 0..SIZE {
+                let patched = i == 0 && (4..12).contains(&row) && (4..12).contains(&col);
+                let c = (row / 2) * SIZE / 2 + col / 2;
+                let (y, u, v) = if patched {
+                    (200, 90, 170)
+                } else {
+                    (luma[row * SIZE + col], chroma_u[c], chroma_v[c])
+                };
+                let expected = yuv_reference(y, u, v, color_space, range);
+
+                // Image rows run downwards from the top of the rect.
+                let px = 50 + 200 * i + col * 8 + 4;
+                let py = 200 + (SIZE - 1 - row) * 8 + 4;
+                let actual = pixel(px, py);
+                assert!(
+                    actual
+                        .iter()
+                        .zip(expected.iter())
+                        .all(|(a, e)| (*a as i32 - e).abs() <= 2),
+                    "image {} pixel ({}, {}): {:?} != {:?}",
+                    i,
+                    col,
+                    row,
+                    actual,
+                    expected
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn image_array() {
+    let (device, queue) = block_on(common::setup_with_features(IMAGE_ARRAY_FEATURES));
+    let (device, queue) = (Arc::new(device), Arc::new(queue));
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+    assert_eq!(
+        vger.image_array_supported(),
+        device.features().contains(IMAGE_ARRAY_FEATURES)
+    );
+
+    // Images of every kind: atlased, with their own texture and
+    // sampler options, YUV, and deleted.
+    let checker: Vec<u8> = (0..16)
+        .flat_map(|i| {
+            if (i + i / 4) % 2 == 0 {
+                [255, 128, 0, 255]
+            } else {
+                [0, 64, 255, 255]
+            }
+        })
+        .collect();
+    vger.set_image_atlas(true);
+    let atlased = vger
+        .create_image_pixels(
+            &checker,
+            4,
+            4,
+            ImageFormat::Rgba8Srgb,
+            None,
+            Default::default(),
+        )
+        .unwrap();
+    vger.set_image_atlas(false);
+    let nearest = vger
+        .create_image_pixels(
+            &checker,
+            4,
+            4,
+            ImageFormat::Rgba8Srgb,
+            None,
+            SamplerOptions {
+                filter: ImageFilter::Nearest,
+                wrap: ImageWrap::Repeat,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    let deleted = vger
+        .create_image_pixels(
+            &checker,
+            4,
+            4,
+            ImageFormat::Rgba8Srgb,
+            None,
+            Default::default(),
+        )
+        .unwrap();
+    let luma: Vec<u8> = (0..16).map(|i| 40 + i * 12).collect();
+    let yuv = vger
+        .create_image_yuv(
+            YuvFormat::I420,
+            4,
+            4,
+            YuvColorSpace::Bt709,
+            YuvRange::Limited,
+            &[
+                YuvPlane::new(&luma),
+                YuvPlane::new(&[60, 200, 90, 160]),
+                YuvPlane::new(&[200, 60, 120, 100]),
+            ],
+            Default::default(),
+        )
+        .unwrap();
+
+    let render = |vger: &mut Vger, png_name: &str| {
+        vger.begin(512.0, 512.0, 1.0);
+        let deleted_paint = vger.image_pattern([0.0, 0.0], [64.0, 64.0], 0.0, deleted, 1.0);
+        vger.delete_image(deleted);
+
+        // Interleave the images so the fallback has to split draws.
+        for row in 0..4 {
+            for (i, image) in [atlased, nearest, yuv, atlased, yuv].iter().enumerate() {
+                let origin = [20.0 + 95.0 * i as f32, 20.0 + 120.0 * row as f32];
+                let paint = vger.image_pattern(origin, [32.0, 32.0], 0.0, *image, 1.0);
+                vger.fill_rect(euclid::rect(origin[0], origin[1], 80.0, 80.0), 8.0, paint);
+            }
+            let paint = vger.color_paint(Color::CYAN);
+            vger.fill_circle([490.0, 60.0 + 120.0 * row as f32], 15.0, paint);
+        }
+        vger.fill_rect(euclid::rect(470.0, 470.0, 40.0, 40.0), 0.0, deleted_paint);
+
+        render_test(vger, &device, &queue, png_name, false);
+        read_png(png_name)
+    };
+
+    let with_array = render(&mut vger, "image_array.png");
+    assert!(png_not_black("image_array.png"));
+
+    // Binding one image at a time must draw the same, even once the
+    // deleted image's slot is reused.
+    let reused = vger
+        .create_image_pixels(
+            &checker,
+            4,
+            4,
+            ImageFormat::Rgba8Srgb,
+            None,
+            Default::default(),
+        )
+        .unwrap();
+    vger.set_image_array(false);
+    let without_array = render(&mut vger, "image_array_fallback.png");
+    vger.delete_image(reused);
+
+    let max_diff = with_array
+        .iter()
+        .zip(without_array.iter())
+        .map(|(a, b)| (*a as i32 - *b as i32).abs())
+        .max()
+        .unwrap();
+    assert!(max_diff <= 1, "max difference {}", max_diff);
+}
+
+#[test]
+fn image_array_slots() {
+    let (device, queue) = block_on(common::setup_with_features(IMAGE_ARRAY_FEATURES));
+    let (device, queue) = (Arc::new(device), Arc::new(queue));
+
+    let mut vger = Vger::new(
+        device.clone(),
+        queue.clone(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    );
+    if !vger.image_array_supported() {
+        eprintln!("image_array_slots skipped: the device lacks IMAGE_ARRAY_FEATURES");
+        return;
+    }
+
+    // Solid images, each in its own slot of the array, one reusing a
+    // deleted image's slot and one packed in the atlas.
+    let colors = [
+        [255, 0, 0, 255],
+        [0, 255, 0, 255],
+        [0, 0, 255, 255],
+        [255, 255, 0, 255],
+        [0, 255, 255, 255],
+    ];
+    let create = |vger: &mut Vger, color: [u8; 4]| {
+        vger.create_image_pixels(
+            &color.repeat(16),
+            4,
+            4,
+            ImageFormat::Rgba8Srgb,
+            None,
+            SamplerOptions::default(),
+        )
+        .unwrap()
+    };
+    let mut images = vec![create(&mut vger, colors[0]), create(&mut vger, colors[1])];
+    let deleted = create(&mut vger, [255; 4]);
+    images.push(create(&mut vger, colors[2]));
+    vger.delete_image(deleted);
+    images.push(create(&mut vger, colors[3]));
+    vger.set_image_atlas(true);
+    images.push(create(&mut vger, colors[4]));
+
+    vger.begin(512.0, 512.0, 1.0);
+    for (i, image) in images.iter().enumerate() {
+        let origin = [20.0 + 90.0 * i as f32, 20.0];
+        let paint = vger.image_pattern(origin, [80.0, 80.0], 0.0, *image, 1.0);
+        vger.fill_rect(euclid::rect(origin[0], origin[1], 80.0, 80.0), 0.0, paint);
+    }
+
+    let png_name = "image_array_slots.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+
+    let pixels = read_png(png_name);
+    for (i, color) in colors.iter().enumerate() {
+        let (x, y) = (60 + 90 * i, 60);
+        let p = ((512 - y) * 512 + x) * 4;
+        assert_eq!(&pixels[p..p + 4], color, "image {}", i);
+    }
 }